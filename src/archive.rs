@@ -0,0 +1,297 @@
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, Read, Seek, SeekFrom, Write};
+
+use serde::{Deserialize, Serialize};
+
+use crate::puzzle::{Puzzle, SolveResult};
+use crate::puzzle_num_format::PuzzleNumFormat;
+use crate::searcher::first_remove;
+
+const MAGIC: &[u8; 4] = b"PCAR";
+const VERSION: u32 = 1;
+const HEADER_LEN: u64 = 8;
+/// Longest record body `encode` writes: two fields of up to `u16::MAX` bytes with their
+/// lengths, the stats flag and the stats
+const MAX_RECORD: usize = 2 * (2 + u16::MAX as usize) + 1 + 16;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SolveStats {
+    pub first: u32,
+    pub shrink: u32,
+    pub all: u32,
+    /// States visited by the solver
+    pub states: u32,
+}
+impl SolveStats {
+    /// `None` for unsolvable puzzles
+    pub fn from_result(puzzle: &Puzzle, result: &SolveResult) -> Option<Self> {
+        if !result.ok {
+            return None;
+        }
+        let moves = result.moves(puzzle);
+        let shrink_moves = result.shrink_move(&moves);
+        Some(Self {
+            first: first_remove(&shrink_moves) as u32,
+            shrink: shrink_moves.len() as u32,
+            all: moves.len() as u32,
+            states: result.states() as u32,
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ArchiveRecord {
+    /// Normalized once appended
+    pub code: PuzzleNumFormat,
+    /// `EvalValue::to_path`, empty if not evaluated
+    pub value: String,
+    pub stats: Option<SolveStats>,
+}
+impl ArchiveRecord {
+    fn encode(&self) -> Result<Vec<u8>, String> {
+        let packed = self.code.to_packed()?;
+        let field_len = |len: usize| {
+            u16::try_from(len).map_err(|_| format!("{} bytes do not fit a record field", len))
+        };
+        let mut body = vec![];
+        body.extend(field_len(packed.len())?.to_le_bytes());
+        body.extend(packed);
+        body.extend(field_len(self.value.len())?.to_le_bytes());
+        body.extend(self.value.as_bytes());
+        match self.stats {
+            Some(stats) => {
+                body.push(1);
+                for x in [stats.first, stats.shrink, stats.all, stats.states] {
+                    body.extend(x.to_le_bytes());
+                }
+            }
+            None => body.push(0),
+        }
+        let mut bytes = (body.len() as u32).to_le_bytes().to_vec();
+        bytes.extend(body);
+        Ok(bytes)
+    }
+    fn decode(body: &[u8]) -> Result<Self, String> {
+        let mut at = 0;
+        let mut take = |n: usize| {
+            let bytes = body.get(at..at + n).ok_or("truncated record");
+            at += n;
+            bytes
+        };
+        let len = u16::from_le_bytes(take(2)?.try_into().unwrap()) as usize;
+        let code = PuzzleNumFormat::from_packed(take(len)?)?;
+        let len = u16::from_le_bytes(take(2)?.try_into().unwrap()) as usize;
+        let value = String::from_utf8(take(len)?.to_vec()).map_err(|e| e.to_string())?;
+        let stats = match take(1)?[0] {
+            0 => None,
+            _ => {
+                let mut read = || -> Result<u32, String> {
+                    Ok(u32::from_le_bytes(take(4)?.try_into().unwrap()))
+                };
+                Some(SolveStats {
+                    first: read()?,
+                    shrink: read()?,
+                    all: read()?,
+                    states: read()?,
+                })
+            }
+        };
+        Ok(Self { code, value, stats })
+    }
+}
+
+/// Streams `(offset, record)` pairs; stops at a truncated last record
+pub struct ArchiveReader<R: Read> {
+    reader: R,
+    offset: u64,
+}
+impl<R: Read> ArchiveReader<R> {
+    pub fn new(mut reader: R) -> Result<Self, String> {
+        let mut header = [0; HEADER_LEN as usize];
+        reader
+            .read_exact(&mut header)
+            .map_err(|_| "missing archive header".to_owned())?;
+        if &header[0..4] != MAGIC {
+            return Err("not a puzzle archive".to_owned());
+        }
+        let version = u32::from_le_bytes(header[4..8].try_into().unwrap());
+        if version > VERSION {
+            return Err(format!("unsupported archive version {}", version));
+        }
+        Ok(Self {
+            reader,
+            offset: HEADER_LEN,
+        })
+    }
+}
+impl<R: Read> Iterator for ArchiveReader<R> {
+    type Item = Result<(u64, ArchiveRecord), String>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut len = [0; 4];
+        self.reader.read_exact(&mut len).ok()?;
+        let len = u32::from_le_bytes(len) as usize;
+        if len > MAX_RECORD {
+            return Some(Err(format!("corrupt record of {} bytes", len)));
+        }
+        let mut body = vec![0; len];
+        self.reader.read_exact(&mut body).ok()?;
+        let offset = self.offset;
+        self.offset += 4 + len as u64;
+        Some(ArchiveRecord::decode(&body).map(|record| (offset, record)))
+    }
+}
+
+/// Append-only file of normalized puzzles, indexed by block code
+pub struct Archive {
+    file: File,
+    index: HashMap<String, u64>,
+    end: u64,
+}
+impl Archive {
+    /// Creates the file if missing and drops a truncated last record
+    pub fn open(path: &str) -> Result<Self, String> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)
+            .map_err(|e| format!("{}: {}", path, e))?;
+        let len = file.metadata().map_err(|e| e.to_string())?.len();
+        if len == 0 {
+            file.write_all(MAGIC).map_err(|e| e.to_string())?;
+            file.write_all(&VERSION.to_le_bytes())
+                .map_err(|e| e.to_string())?;
+        }
+        file.seek(SeekFrom::Start(0)).map_err(|e| e.to_string())?;
+        let mut index = HashMap::new();
+        let mut reader = ArchiveReader::new(BufReader::new(&mut file))?;
+        for entry in reader.by_ref() {
+            let (offset, record) = entry?;
+            index.insert(record.code.to_block_code(), offset);
+        }
+        let end = reader.offset;
+        if end < len {
+            println!("WARN: dropping {} trailing bytes of {}", len - end, path);
+            file.set_len(end).map_err(|e| e.to_string())?;
+        }
+        Ok(Self { file, index, end })
+    }
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+    /// Normalizes the record; `false` if the puzzle is already stored
+    pub fn append(&mut self, mut record: ArchiveRecord) -> Result<bool, String> {
+        record.code = record.code.normalize();
+        let code = record.code.to_block_code();
+        if self.index.contains_key(&code) {
+            return Ok(false);
+        }
+        let bytes = record.encode()?;
+        self.file
+            .seek(SeekFrom::Start(self.end))
+            .map_err(|e| e.to_string())?;
+        self.file.write_all(&bytes).map_err(|e| e.to_string())?;
+        self.index.insert(code, self.end);
+        self.end += bytes.len() as u64;
+        Ok(true)
+    }
+    pub fn get(&mut self, code: &PuzzleNumFormat) -> Result<Option<ArchiveRecord>, String> {
+        let Some(&offset) = self.index.get(&code.normalize().to_block_code()) else {
+            return Ok(None);
+        };
+        self.file
+            .seek(SeekFrom::Start(offset))
+            .map_err(|e| e.to_string())?;
+        let mut len = [0; 4];
+        self.file.read_exact(&mut len).map_err(|e| e.to_string())?;
+        let len = u32::from_le_bytes(len) as usize;
+        if len > MAX_RECORD {
+            return Err(format!("corrupt record of {} bytes", len));
+        }
+        let mut body = vec![0; len];
+        self.file.read_exact(&mut body).map_err(|e| e.to_string())?;
+        ArchiveRecord::decode(&body).map(Some)
+    }
+}
+
+/// Streams every record of the archive at `path`
+pub fn read_archive(path: &str) -> Result<ArchiveReader<BufReader<File>>, String> {
+    let file = File::open(path).map_err(|e| format!("{}: {}", path, e))?;
+    ArchiveReader::new(BufReader::new(file))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::sample_puzzle;
+    #[test]
+    fn test_archive() {
+        let path = std::env::temp_dir().join(format!("archive_{}.pcar", std::process::id()));
+        let path = path.to_string_lossy().to_string();
+        let puzzle = sample_puzzle();
+        let result = puzzle.solve();
+        let record = ArchiveRecord {
+            code: PuzzleNumFormat::from_puzzle(&puzzle),
+            value: "F1S2A3".to_owned(),
+            stats: SolveStats::from_result(&puzzle, &result),
+        };
+        let other = ArchiveRecord {
+            code: PuzzleNumFormat::from_block_code("222:2:01000200"),
+            value: String::new(),
+            stats: None,
+        };
+        {
+            let mut archive = Archive::open(&path).unwrap();
+            assert!(archive.append(record.clone()).unwrap());
+            assert!(archive.append(other.clone()).unwrap());
+            // same puzzle with other piece numbers
+            let swapped = PuzzleNumFormat::from_block_code("222:2:02000100");
+            assert!(!archive
+                .append(ArchiveRecord {
+                    code: swapped,
+                    ..other.clone()
+                })
+                .unwrap());
+        }
+        // simulate an interrupted append
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(&[9, 0, 0, 0, 1]).unwrap();
+        drop(file);
+
+        let mut archive = Archive::open(&path).unwrap();
+        assert_eq!(archive.len(), 2);
+        let found = archive.get(&record.code).unwrap().unwrap();
+        assert_eq!(found.code, record.code.normalize());
+        assert_eq!(found.stats, record.stats);
+        assert_eq!(
+            found.stats.unwrap().all as usize,
+            result.moves(&puzzle).len()
+        );
+        assert!(archive
+            .get(&PuzzleNumFormat::from_block_code("222:1:11111111"))
+            .unwrap()
+            .is_none());
+        let records = read_archive(&path).unwrap().collect::<Result<Vec<_>, _>>();
+        std::fs::remove_file(&path).unwrap();
+        let records = records.unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].1.value, "");
+    }
+    #[test]
+    fn test_archive_limits() {
+        let record = ArchiveRecord {
+            code: PuzzleNumFormat::from_block_code("222:2:01000200"),
+            value: "F".repeat(u16::MAX as usize + 1),
+            stats: None,
+        };
+        assert!(record.encode().is_err());
+        // a corrupt length is not allocated
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend(VERSION.to_le_bytes());
+        bytes.extend(u32::MAX.to_le_bytes());
+        let mut reader = ArchiveReader::new(&bytes[..]).unwrap();
+        assert!(reader.next().unwrap().is_err());
+    }
+}
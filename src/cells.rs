@@ -110,6 +110,42 @@ impl Cells {
         }
         count == self.count()
     }
+    /// Connected parts, largest first
+    pub fn components(&self) -> Vec<Cells> {
+        let mut rest = self.clone();
+        let mut parts = vec![];
+        while let Some(start) = V3Iter::cube(self.size).find(|&x| rest.getv(x)) {
+            let mut part = Cells::empty(self.size);
+            let mut queue = std::collections::VecDeque::from([start]);
+            part.setv(start, true);
+            rest.setv(start, false);
+            while let Some(x) = queue.pop_front() {
+                for d in D6 {
+                    let Some(n) = (V3I::from(x) + d).into_v3_in(&V3::cube(self.size)) else {
+                        continue;
+                    };
+                    if rest.getv(n) {
+                        rest.setv(n, false);
+                        part.setv(n, true);
+                        queue.push_back(n);
+                    }
+                }
+            }
+            parts.push(part);
+        }
+        parts.sort_by_key(|part| std::cmp::Reverse(part.count()));
+        parts
+    }
+    /// Shares a face with `other`
+    pub fn touches(&self, other: &Cells) -> bool {
+        V3Iter::cube(self.size).filter(|&x| self.getv(x)).any(|x| {
+            D6.iter().any(|&d| {
+                (V3I::from(x) + d)
+                    .into_v3_in(&V3::cube(self.size))
+                    .is_some_and(|n| other.getv(n))
+            })
+        })
+    }
     pub fn count(&self) -> usize {
         self.bits.count_ones()
     }
@@ -119,6 +155,9 @@ impl Cells {
     pub fn and_inplace(&mut self, other: &Cells) {
         self.bits.and_inplace(&other.bits);
     }
+    pub fn and_not_inplace(&mut self, other: &Cells) {
+        self.bits = self.bits.difference_cloned(&other.bits);
+    }
     pub fn overlap(&self, other: &Cells) -> bool {
         self.bits.and_cloned(&other.bits).any()
     }
@@ -0,0 +1,184 @@
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::puzzle::Puzzle;
+use crate::puzzle_num_format::PuzzleNumFormat;
+use crate::searcher::SearchRng;
+use crate::strategy::Chain;
+
+/// Bumped on incompatible changes of `Checkpoint`
+pub const CHECKPOINT_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChainJson<V> {
+    pub code: String,
+    pub value: V,
+    /// Tries since the last improvement
+    pub count: usize,
+}
+impl<V: Copy> ChainJson<V> {
+    pub fn new(puzzle: &Puzzle, value: V, count: usize) -> Self {
+        Self {
+            code: PuzzleNumFormat::from_puzzle(puzzle).to_block_code(),
+            value,
+            count,
+        }
+    }
+    pub fn from_chain(chain: &Chain<V>) -> Self {
+        Self::new(&chain.puzzle, chain.value, chain.count)
+    }
+    /// Takes the settings other than the pieces from `initial`
+    pub fn to_chain(&self, initial: &Puzzle) -> Chain<V> {
+        let mut puzzle = initial.clone();
+        puzzle.pieces = PuzzleNumFormat::from_block_code(&self.code)
+            .to_puzzle()
+            .pieces;
+        Chain {
+            puzzle,
+            value: self.value,
+            count: self.count,
+        }
+    }
+}
+
+/// State of one search thread, enough to continue it after a restart
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Checkpoint<V> {
+    pub version: u32,
+    /// Tries (or generations) done by the interrupted search
+    pub tries: usize,
+    pub chains: Vec<ChainJson<V>>,
+    /// Best puzzle already sent to the writer
+    pub best: Option<ChainJson<V>>,
+    pub rng: SearchRng,
+}
+
+/// Checkpoint file of one `Launcher` thread
+#[derive(Debug)]
+pub struct Checkpointer<V> {
+    path: String,
+    /// Tries between two saves
+    interval: usize,
+    /// Loaded on open, handed to the first search
+    resume: Option<Checkpoint<V>>,
+}
+impl<V: Serialize + DeserializeOwned> Checkpointer<V> {
+    /// Loads the existing checkpoint only if `resume`, it is overwritten otherwise
+    pub fn open(dir: &str, thread: usize, interval: usize, resume: bool) -> Result<Self, String> {
+        std::fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir, e))?;
+        let path = format!("{}/thread_{}.json", dir, thread);
+        let text = std::fs::read_to_string(&path).ok().filter(|_| resume);
+        let resume = match text {
+            Some(text) => {
+                let checkpoint: Checkpoint<V> =
+                    serde_json::from_str(&text).map_err(|e| format!("{}: {}", path, e))?;
+                if checkpoint.version != CHECKPOINT_VERSION {
+                    return Err(format!(
+                        "{}: unsupported version {}",
+                        path, checkpoint.version
+                    ));
+                }
+                println!("INFO: resume from {} at try {}", path, checkpoint.tries);
+                Some(checkpoint)
+            }
+            None => None,
+        };
+        Ok(Self {
+            path,
+            interval: interval.max(1),
+            resume,
+        })
+    }
+    pub fn take(&mut self) -> Option<Checkpoint<V>> {
+        self.resume.take()
+    }
+    /// Whether to save after `tries` tries
+    pub fn due(&self, tries: usize) -> bool {
+        tries.is_multiple_of(self.interval)
+    }
+    /// Replaces the file at once, so that a crash keeps the previous checkpoint
+    pub fn save(&self, checkpoint: &Checkpoint<V>) -> Result<(), String> {
+        let tmp = format!("{}.tmp", self.path);
+        let text = serde_json::to_string(checkpoint).map_err(|e| e.to_string())?;
+        std::fs::write(&tmp, text).map_err(|e| format!("{}: {}", tmp, e))?;
+        std::fs::rename(&tmp, &self.path).map_err(|e| format!("{}: {}", self.path, e))
+    }
+}
+
+/// Command line of a checkpointed run, replayed by `resume`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunJson {
+    pub args: Vec<String>,
+    pub seed: u64,
+}
+impl RunJson {
+    pub fn load(dir: &str) -> Result<Self, String> {
+        let path = format!("{}/run.json", dir);
+        let text = std::fs::read_to_string(&path).map_err(|e| format!("{}: {}", path, e))?;
+        serde_json::from_str(&text).map_err(|e| format!("{}: {}", path, e))
+    }
+    pub fn save(&self, dir: &str) -> Result<(), String> {
+        std::fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir, e))?;
+        let path = format!("{}/run.json", dir);
+        let text = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        std::fs::write(&path, text).map_err(|e| format!("{}: {}", path, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::searcher::ShrinkStepValue;
+    use crate::server::sample_puzzle;
+    use rand::{Rng, SeedableRng};
+    #[test]
+    fn test_checkpoint() {
+        let dir = std::env::temp_dir().join(format!("checkpoint_{}", std::process::id()));
+        let dir = dir.to_string_lossy().to_string();
+        let mut initial = sample_puzzle();
+        initial.reach_limit = Some(1000);
+        let chain = Chain {
+            puzzle: sample_puzzle(),
+            value: ShrinkStepValue(3, 4, 5),
+            count: 7,
+        };
+        let mut rnd = SearchRng::seed_from_u64(1);
+        rnd.gen::<u64>();
+        {
+            let mut checkpointer =
+                Checkpointer::<ShrinkStepValue>::open(&dir, 0, 10, true).unwrap();
+            assert!(checkpointer.take().is_none());
+            assert!(checkpointer.due(20) && !checkpointer.due(21));
+            let checkpoint = Checkpoint {
+                version: CHECKPOINT_VERSION,
+                tries: 20,
+                chains: vec![ChainJson::from_chain(&chain)],
+                best: None,
+                rng: rnd.clone(),
+            };
+            checkpointer.save(&checkpoint).unwrap();
+        }
+        let mut fresh = Checkpointer::<ShrinkStepValue>::open(&dir, 0, 10, false).unwrap();
+        assert!(fresh.take().is_none());
+        let mut checkpointer = Checkpointer::<ShrinkStepValue>::open(&dir, 0, 10, true).unwrap();
+        let run = RunJson {
+            args: vec!["solver".to_owned(), "generate_file".to_owned()],
+            seed: 3,
+        };
+        run.save(&dir).unwrap();
+        let loaded = RunJson::load(&dir);
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(loaded.unwrap().args, run.args);
+        let checkpoint = checkpointer.take().unwrap();
+        assert_eq!(checkpoint.tries, 20);
+        let restored = checkpoint.chains[0].to_chain(&initial);
+        assert_eq!(restored.value, chain.value);
+        assert_eq!(restored.count, 7);
+        assert_eq!(restored.puzzle.reach_limit, Some(1000));
+        assert_eq!(
+            PuzzleNumFormat::from_puzzle(&restored.puzzle),
+            PuzzleNumFormat::from_puzzle(&chain.puzzle)
+        );
+        // continues the same random sequence
+        assert_eq!(checkpoint.rng.clone().gen::<u64>(), rnd.gen::<u64>());
+    }
+}
@@ -0,0 +1,271 @@
+use std::collections::VecDeque;
+
+use serde::{Deserialize, Serialize};
+
+use crate::cells::{Cells, D6};
+use crate::iters::V3Iter;
+use crate::printability::Printability;
+use crate::searcher::PuzzleConstraints;
+use crate::v3::{V3, V3I};
+
+/// Every constraint of `of` holds
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AndConstraints<C: PuzzleConstraints> {
+    pub of: Vec<C>,
+}
+impl<C: PuzzleConstraints> PuzzleConstraints for AndConstraints<C> {
+    fn is_ok(&self, blocks: &[Cells]) -> bool {
+        self.of.iter().all(|c| c.is_ok(blocks))
+    }
+}
+/// Some constraint of `of` holds
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct OrConstraints<C: PuzzleConstraints> {
+    pub of: Vec<C>,
+}
+impl<C: PuzzleConstraints> PuzzleConstraints for OrConstraints<C> {
+    fn is_ok(&self, blocks: &[Cells]) -> bool {
+        self.of.iter().any(|c| c.is_ok(blocks))
+    }
+}
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct NotConstraints<C: PuzzleConstraints> {
+    pub of: Box<C>,
+}
+impl<C: PuzzleConstraints> PuzzleConstraints for NotConstraints<C> {
+    fn is_ok(&self, blocks: &[Cells]) -> bool {
+        !self.of.is_ok(blocks)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MaxPieceSizeConstraints {
+    pub size: usize,
+}
+impl PuzzleConstraints for MaxPieceSizeConstraints {
+    fn is_ok(&self, blocks: &[Cells]) -> bool {
+        blocks.iter().all(|block| block.count() <= self.size)
+    }
+}
+
+/// The largest piece has at most `ratio` times the cells of the smallest one
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BalanceConstraints {
+    pub ratio: f64,
+}
+impl PuzzleConstraints for BalanceConstraints {
+    fn is_ok(&self, blocks: &[Cells]) -> bool {
+        let counts = blocks.iter().map(Cells::count);
+        let (Some(min), Some(max)) = (counts.clone().min(), counts.max()) else {
+            return true;
+        };
+        max as f64 <= min as f64 * self.ratio
+    }
+}
+
+/// Every piece fits in a box of `size` in some axis-aligned orientation
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MaxBoundingBoxConstraints {
+    pub size: [usize; 3],
+}
+impl PuzzleConstraints for MaxBoundingBoxConstraints {
+    fn is_ok(&self, blocks: &[Cells]) -> bool {
+        let mut limit = self.size;
+        limit.sort();
+        blocks
+            .iter()
+            .filter(|block| block.count() > 0)
+            .all(|block| {
+                let mut extents = extents(block);
+                extents.sort();
+                extents.iter().zip(limit).all(|(&e, l)| e <= l)
+            })
+    }
+}
+
+/// Every piece can be seen from outside, either on the surface of the cube or next to
+/// an empty cell connected to the outside
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct NoEnclosedConstraints {}
+impl PuzzleConstraints for NoEnclosedConstraints {
+    fn is_ok(&self, blocks: &[Cells]) -> bool {
+        let Some(size) = blocks.first().map(|block| block.size) else {
+            return true;
+        };
+        let outside = outside(blocks, size);
+        blocks.iter().all(|block| {
+            V3Iter::cube(size).filter(|&x| block.getv(x)).any(|x| {
+                on_surface(x, size)
+                    || D6.iter().any(|&d| {
+                        (V3I::from(x) + d)
+                            .into_v3_in(&V3::cube(size))
+                            .is_some_and(|n| outside.getv(n))
+                    })
+            })
+        })
+    }
+}
+
+/// Every piece is one cell thick
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PlanarConstraints {}
+impl PuzzleConstraints for PlanarConstraints {
+    fn is_ok(&self, blocks: &[Cells]) -> bool {
+        blocks
+            .iter()
+            .filter(|block| block.count() > 0)
+            .all(|block| extents(block).contains(&1))
+    }
+}
+
+/// Each of the 6 faces of the cube is touched by at least `pieces` pieces
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FaceTouchConstraints {
+    pub pieces: usize,
+}
+impl PuzzleConstraints for FaceTouchConstraints {
+    fn is_ok(&self, blocks: &[Cells]) -> bool {
+        let Some(size) = blocks.first().map(|block| block.size) else {
+            return self.pieces == 0;
+        };
+        (0..3).all(|axis| {
+            [0, size - 1].iter().all(|&side| {
+                let touching = blocks.iter().filter(|block| {
+                    V3Iter::cube(size).any(|x| coord(x, axis) == side && block.getv(x))
+                });
+                touching.count() >= self.pieces
+            })
+        })
+    }
+}
+
+/// Every piece prints without support in its best orientation, and without snapping
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PrintableConstraints {
+    pub max_overhangs: usize,
+    /// Thin necks allowed per piece
+    #[serde(default)]
+    pub max_necks: usize,
+}
+impl PuzzleConstraints for PrintableConstraints {
+    fn is_ok(&self, blocks: &[Cells]) -> bool {
+        blocks.iter().all(|block| {
+            let printability = Printability::of(block);
+            printability.overhangs <= self.max_overhangs
+                && printability.necks.len() <= self.max_necks
+        })
+    }
+}
+
+fn coord(x: V3, axis: usize) -> usize {
+    [x.0, x.1, x.2][axis]
+}
+fn on_surface(x: V3, size: usize) -> bool {
+    (0..3).any(|axis| coord(x, axis) == 0 || coord(x, axis) == size - 1)
+}
+/// Cells in each axis of the bounding box
+pub fn extents(block: &Cells) -> [usize; 3] {
+    let (min, max) = block.bounding_box();
+    let size = max - min;
+    [size.0 + 1, size.1 + 1, size.2 + 1]
+}
+/// Empty cells connected to the outside of the cube
+fn outside(blocks: &[Cells], size: usize) -> Cells {
+    let mut filled = Cells::empty(size);
+    for block in blocks {
+        filled.or_inplace(block);
+    }
+    let mut outside = Cells::empty(size);
+    let mut queue = V3Iter::cube(size)
+        .filter(|&x| on_surface(x, size) && !filled.getv(x))
+        .collect::<VecDeque<_>>();
+    for &x in queue.iter() {
+        outside.setv(x, true);
+    }
+    while let Some(x) = queue.pop_front() {
+        for d in D6 {
+            let Some(n) = (V3I::from(x) + d).into_v3_in(&V3::cube(size)) else {
+                continue;
+            };
+            if !filled.getv(n) && !outside.getv(n) {
+                outside.setv(n, true);
+                queue.push_back(n);
+            }
+        }
+    }
+    outside
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_constraints() {
+        let mut core = Cells::empty(3);
+        core.set(1, 1, 1, true);
+        let mut shell = Cells::empty(3);
+        for x in V3Iter::cube(3) {
+            shell.setv(x, x != V3(1, 1, 1));
+        }
+        let enclosed = vec![core.clone(), shell.clone()];
+        assert!(!NoEnclosedConstraints {}.is_ok(&enclosed));
+        // a hole in the shell opens a way to the core
+        let mut open = shell.clone();
+        open.set(1, 1, 0, false);
+        assert!(NoEnclosedConstraints {}.is_ok(&[core.clone(), open.clone()]));
+
+        assert!(MaxPieceSizeConstraints { size: 26 }.is_ok(&enclosed));
+        assert!(!MaxPieceSizeConstraints { size: 25 }.is_ok(&enclosed));
+        assert!(BalanceConstraints { ratio: 26.0 }.is_ok(&enclosed));
+        assert!(!BalanceConstraints { ratio: 25.0 }.is_ok(&enclosed));
+        assert!(MaxBoundingBoxConstraints { size: [3, 3, 3] }.is_ok(&enclosed));
+        assert!(!MaxBoundingBoxConstraints { size: [3, 2, 3] }.is_ok(&enclosed));
+        assert!(FaceTouchConstraints { pieces: 1 }.is_ok(&enclosed));
+        let printable = PrintableConstraints {
+            max_overhangs: 0,
+            max_necks: 0,
+        };
+        assert!(printable.is_ok(&enclosed));
+        let mut bar = Cells::empty(3);
+        for x in 0..3 {
+            bar.set(x, 0, 0, true);
+        }
+        assert!(!printable.is_ok(&[bar.clone()]));
+        let printable = PrintableConstraints {
+            max_overhangs: 0,
+            max_necks: 1,
+        };
+        assert!(printable.is_ok(&[bar]));
+        assert!(!FaceTouchConstraints { pieces: 2 }.is_ok(&enclosed));
+
+        let mut floor = Cells::empty(3);
+        for x in V3Iter::cube(3).filter(|x| x.2 == 0) {
+            floor.setv(x, true);
+        }
+        let mut wall = Cells::empty(3);
+        wall.set(0, 0, 1, true);
+        wall.set(0, 0, 2, true);
+        assert!(PlanarConstraints {}.is_ok(&[floor.clone(), wall.clone(), core.clone()]));
+        assert!(!PlanarConstraints {}.is_ok(&[floor, shell]));
+
+        let planar = PlanarConstraints {};
+        let small = MaxPieceSizeConstraints { size: 1 };
+        assert!(OrConstraints {
+            of: vec![small.clone()]
+        }
+        .is_ok(&[core.clone()]));
+        assert!(!OrConstraints {
+            of: vec![small.clone()]
+        }
+        .is_ok(&[wall.clone()]));
+        assert!(AndConstraints::<PlanarConstraints> { of: vec![] }.is_ok(&[wall.clone()]));
+        assert!(NotConstraints {
+            of: Box::new(small)
+        }
+        .is_ok(&[wall.clone()]));
+        assert!(!NotConstraints {
+            of: Box::new(planar)
+        }
+        .is_ok(&[wall]));
+    }
+}
@@ -0,0 +1,232 @@
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+
+use crate::cells::D6;
+use crate::constraints::extents;
+use crate::iters::V3Iter;
+use crate::puzzle::{Puzzle, ShrinkMove, SolveResult};
+use crate::puzzle_num_format::PuzzleNumFormat;
+use crate::searcher::{drop_count, first_remove};
+use crate::server::PuzzleJson;
+use crate::v3::{V3, V3I};
+
+/// Named features of a solved puzzle, for the evaluators and offline analysis
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Features {
+    /// Shrunk moves before each removal, e.g. 5.3.1.0.0
+    pub levels: Vec<usize>,
+    pub first_remove: usize,
+    pub drop_count: usize,
+    /// Shrunk moves
+    pub shrink: usize,
+    /// Unit moves
+    pub all: usize,
+    /// Shrunk moves shifting more than one piece
+    pub multi_moves: usize,
+    /// Touching faces of each pair of pieces in the assembled puzzle, touching pairs only
+    pub contacts: Vec<(usize, usize, usize)>,
+    /// Cells of each piece
+    pub sizes: Vec<usize>,
+    pub size_variance: f64,
+    /// Cells in each axis of the bounding box of each piece
+    pub bounding_boxes: Vec<[usize; 3]>,
+    /// Solver states without a new next state
+    pub dead_ends: usize,
+    /// Empty cells of the whole cube, with the cells outside the shape of a shape puzzle
+    #[serde(alias = "holes")]
+    pub cube_holes: usize,
+}
+impl Features {
+    pub fn of(puzzle: &Puzzle, result: &SolveResult) -> Features {
+        let blocks = puzzle.pieces.iter().map(|p| &p.block).collect_vec();
+        let size = puzzle.size;
+        let contacts = (0..blocks.len())
+            .tuple_combinations()
+            .map(|(a, b)| {
+                let area = V3Iter::cube(size)
+                    .filter(|&x| blocks[a].getv(x))
+                    .map(|x| {
+                        D6.iter()
+                            .filter_map(|&d| (V3I::from(x) + d).into_v3_in(&V3::cube(size)))
+                            .filter(|&n| blocks[b].getv(n))
+                            .count()
+                    })
+                    .sum();
+                (a, b, area)
+            })
+            .filter(|&(_, _, area)| area > 0)
+            .collect();
+        Features {
+            contacts,
+            bounding_boxes: blocks.iter().map(|b| extents(b)).collect(),
+            ..Self::of_moves(puzzle, result)
+        }
+    }
+    /// `of` without the pairwise contacts and the bounding boxes, left empty: what the
+    /// evaluators use on every try
+    pub fn of_moves(puzzle: &Puzzle, result: &SolveResult) -> Features {
+        let moves = result.moves(puzzle);
+        let shrink_moves = result.shrink_move(&moves);
+        let mut levels = vec![];
+        let mut shifts = 0;
+        for mov in shrink_moves.iter() {
+            match mov {
+                ShrinkMove::Shift(_, _) => shifts += 1,
+                ShrinkMove::Remove(_, _) => {
+                    levels.push(shifts);
+                    shifts = 0;
+                }
+            }
+        }
+        let multi_moves = shrink_moves
+            .iter()
+            .filter(|mov| matches!(mov, ShrinkMove::Shift(pieces, _) if pieces.len() > 1))
+            .count();
+        let blocks = puzzle.pieces.iter().map(|p| &p.block).collect_vec();
+        let sizes = blocks.iter().map(|b| b.count()).collect_vec();
+        let mean = sizes.iter().sum::<usize>() as f64 / sizes.len().max(1) as f64;
+        let size_variance = sizes
+            .iter()
+            .map(|&s| (s as f64 - mean).powi(2))
+            .sum::<f64>()
+            / sizes.len().max(1) as f64;
+        let size = puzzle.size;
+        Features {
+            levels,
+            first_remove: first_remove(&shrink_moves),
+            drop_count: drop_count(&shrink_moves),
+            shrink: shrink_moves.len(),
+            all: moves.len(),
+            multi_moves,
+            contacts: vec![],
+            size_variance,
+            bounding_boxes: vec![],
+            dead_ends: result.dead_ends(),
+            cube_holes: size * size * size - sizes.iter().sum::<usize>(),
+            sizes,
+        }
+    }
+
+    /// Column names and values, lists joined with `.` and pairs as `a-b:area`
+    fn columns(&self) -> Vec<(&'static str, String)> {
+        let join = |xs: &[usize]| xs.iter().join(".");
+        vec![
+            ("levels", join(&self.levels)),
+            ("first_remove", self.first_remove.to_string()),
+            ("drop_count", self.drop_count.to_string()),
+            ("shrink", self.shrink.to_string()),
+            ("all", self.all.to_string()),
+            ("multi_moves", self.multi_moves.to_string()),
+            (
+                "contacts",
+                self.contacts
+                    .iter()
+                    .map(|(a, b, area)| format!("{}-{}:{}", a, b, area))
+                    .join(" "),
+            ),
+            ("sizes", join(&self.sizes)),
+            ("size_variance", format!("{:.3}", self.size_variance)),
+            (
+                "bounding_boxes",
+                self.bounding_boxes
+                    .iter()
+                    .map(|[x, y, z]| format!("{}x{}x{}", x, y, z))
+                    .join(" "),
+            ),
+            ("dead_ends", self.dead_ends.to_string()),
+            ("cube_holes", self.cube_holes.to_string()),
+        ]
+    }
+}
+
+/// One row per puzzle; features missing in puzzles written before them are computed again
+/// from the code
+pub fn to_csv(puzzles: &[PuzzleJson]) -> Result<String, String> {
+    let mut rows = vec![];
+    for json in puzzles {
+        let features = match &json.features {
+            Some(features) => features.clone(),
+            None => {
+                let puzzle = PuzzleNumFormat::from_block_code(&json.code).to_puzzle();
+                let result = puzzle.solve();
+                if !result.ok {
+                    return Err(format!("{} has no solution", json.name));
+                }
+                Features::of(&puzzle, &result)
+            }
+        };
+        let mut row = vec![json.name.clone(), json.run.clone(), json.code.clone()];
+        row.extend(features.columns().into_iter().map(|(_, value)| value));
+        rows.push(row.iter().map(|field| csv_field(field)).join(","));
+    }
+    let header = ["name", "run", "code"]
+        .into_iter()
+        .chain(
+            Features::default()
+                .columns()
+                .into_iter()
+                .map(|(name, _)| name),
+        )
+        .join(",");
+    Ok(std::iter::once(header).chain(rows).join("\n") + "\n")
+}
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::sample_puzzle;
+    #[test]
+    fn test_features() {
+        // as read back from its code, with the default solver options
+        let puzzle = PuzzleNumFormat::from_puzzle(&sample_puzzle()).to_puzzle();
+        let result = puzzle.solve();
+        let features = Features::of(&puzzle, &result);
+        assert_eq!(features.levels[0], features.first_remove);
+        assert_eq!(features.levels.len(), puzzle.pieces.len());
+        assert_eq!(
+            features.levels.iter().sum::<usize>() + features.levels.len(),
+            features.shrink
+        );
+        assert_eq!(features.sizes.len(), puzzle.pieces.len());
+        assert_eq!(
+            features.sizes.iter().sum::<usize>() + features.cube_holes,
+            puzzle.size.pow(3)
+        );
+        assert!(features.multi_moves <= features.shrink);
+        assert!(!features.contacts.is_empty());
+        assert!(features
+            .bounding_boxes
+            .iter()
+            .zip(features.sizes.iter())
+            .all(|([x, y, z], &s)| x * y * z >= s));
+        let moves_only = Features::of_moves(&puzzle, &result);
+        assert!(moves_only.contacts.is_empty() && moves_only.bounding_boxes.is_empty());
+        assert_eq!(
+            Features {
+                contacts: features.contacts.clone(),
+                bounding_boxes: features.bounding_boxes.clone(),
+                ..moves_only
+            },
+            features
+        );
+
+        let mut json = PuzzleJson::from_result(&puzzle, &result);
+        assert_eq!(json.features.as_ref(), Some(&features));
+        let csv = to_csv(&[json.clone()]).unwrap();
+        // recomputed when missing
+        json.features = None;
+        assert_eq!(to_csv(&[json]).unwrap(), csv);
+        let lines = csv.lines().collect_vec();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("name,run,code,levels,first_remove"));
+        let levels = features.levels.iter().join(".");
+        assert!(lines[1].contains(&format!(",{},{},", levels, features.first_remove)));
+    }
+}
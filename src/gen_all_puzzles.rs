@@ -1,4 +1,8 @@
-use crate::{puzzle_num_format::PuzzleNumFormat, v3::V3};
+use crate::{
+    archive::{Archive, ArchiveRecord},
+    puzzle_num_format::PuzzleNumFormat,
+    v3::V3,
+};
 
 pub struct GenAllPuzzles {
     pub size: usize,
@@ -60,6 +64,16 @@ impl GenAllWriter for DebugWriter {
         }
     }
 }
+impl GenAllWriter for Archive {
+    fn write_puzzle(&mut self, puzzle: &PuzzleNumFormat) {
+        let record = ArchiveRecord {
+            code: puzzle.clone(),
+            value: String::new(),
+            stats: None,
+        };
+        self.append(record).unwrap();
+    }
+}
 
 #[cfg(test)]
 mod tests {
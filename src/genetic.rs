@@ -0,0 +1,396 @@
+use std::sync::{mpsc::Sender, Arc};
+
+use itertools::Itertools;
+use rand::Rng;
+
+use crate::cells::Cells;
+use crate::checkpoint::{ChainJson, Checkpoint, Checkpointer, CHECKPOINT_VERSION};
+use crate::island::Island;
+use crate::iters::V3Iter;
+use crate::puzzle::{merge_stray_parts, Piece, Puzzle};
+use crate::searcher::{
+    EvalValue, Evaluator, PuzzleConstraints, PuzzleGenerator, SearchRng, Searcher,
+    TerminalPuzzleConstraints,
+};
+use crate::solve_cache::SolveCache;
+use crate::v3::V3;
+
+/// Attempts before a crossover falls back to copying the first parent
+const CROSSOVER_RETRY: usize = 10;
+
+fn overlap(a: &Cells, b: &Cells) -> usize {
+    let mut both = a.clone();
+    both.and_inplace(b);
+    both.count()
+}
+
+/// Renumbers the pieces of `b` so that each matches the most overlapping piece of `a`
+fn align_pieces(a: &Puzzle, b: &Puzzle) -> Vec<Cells> {
+    let mut order = (0..a.pieces.len()).collect_vec();
+    order.sort_by_key(|&i| std::cmp::Reverse(a.pieces[i].block.count()));
+    let mut unused = (0..b.pieces.len()).collect_vec();
+    let mut aligned = vec![Cells::empty(a.size); a.pieces.len()];
+    for i in order {
+        let (k, &j) = unused
+            .iter()
+            .enumerate()
+            .max_by_key(|(_, &j)| overlap(&a.pieces[i].block, &b.pieces[j].block))
+            .unwrap();
+        aligned[i] = b.pieces[j].block.clone();
+        unused.remove(k);
+    }
+    aligned
+}
+
+/// Cells on one side of a random axis-aligned cut
+fn random_half(size: usize, rnd: &mut impl Rng) -> Cells {
+    let axis = rnd.gen_range(0..3);
+    let cut = rnd.gen_range(1..size.max(2));
+    let mut half = Cells::empty(size);
+    for v in V3Iter::cube(size) {
+        let V3(x, y, z) = v;
+        if [x, y, z][axis] < cut {
+            half.setv(v, true);
+        }
+    }
+    half
+}
+
+/// Takes one side of a cut from `a` and the other from `b`, then reconnects the pieces.
+/// The child keeps the cell count of `a`, no piece gets smaller than in either parent and
+/// the pieces meet `constraints`.
+pub fn crossover(
+    a: &Puzzle,
+    b: &Puzzle,
+    constraints: &impl PuzzleConstraints,
+    rnd: &mut impl Rng,
+) -> Option<Puzzle> {
+    // structural mutations change the piece count
+    if a.pieces.len() != b.pieces.len() {
+        return None;
+    }
+    let aligned = align_pieces(a, b);
+    let total: usize = a.pieces.iter().map(|p| p.block.count()).sum();
+    let min_piece = a
+        .pieces
+        .iter()
+        .map(|p| p.block.count())
+        .chain(aligned.iter().map(|b| b.count()))
+        .min()
+        .unwrap_or(0);
+    for _ in 0..CROSSOVER_RETRY {
+        let half = random_half(a.size, rnd);
+        let mut blocks = a
+            .pieces
+            .iter()
+            .zip(aligned.iter())
+            .map(|(piece, other)| {
+                let mut block = piece.block.clone();
+                block.and_inplace(&half);
+                let mut rest = other.clone();
+                rest.and_not_inplace(&half);
+                block.or_inplace(&rest);
+                block
+            })
+            .collect_vec();
+        merge_stray_parts(&mut blocks);
+        // parts touching no other piece are dropped
+        for block in blocks.iter_mut() {
+            if let Some(largest) = block.components().into_iter().next() {
+                *block = largest;
+            }
+        }
+        if blocks.iter().map(Cells::count).sum::<usize>() != total
+            || blocks.iter().any(|block| block.count() < min_piece.max(1))
+            || !constraints.is_ok(&blocks)
+        {
+            continue;
+        }
+        let mut child = a.clone();
+        child.pieces = blocks.iter().map(Piece::from_block).collect();
+        return Some(child);
+    }
+    None
+}
+
+/// Population based alternative to `PuzzleSearcher`
+#[derive(Debug, Clone)]
+pub struct GeneticSearcher<
+    G: PuzzleGenerator,
+    E: Evaluator,
+    C: PuzzleConstraints = TerminalPuzzleConstraints,
+> {
+    population: usize,
+    generations: usize,
+    /// Best individuals copied unchanged to the next generation
+    elite: usize,
+    /// Individuals compared to pick each parent
+    tournament: usize,
+    give_up: usize,
+    initial: Puzzle,
+    mutation: G,
+    pub evaluator: E,
+    /// Checked on the crossover children, usually those of `mutation`
+    constraints: C,
+    cache: Option<Arc<SolveCache>>,
+}
+impl<G: PuzzleGenerator, E: Evaluator> GeneticSearcher<G, E> {
+    pub fn new(
+        population: usize,
+        generations: usize,
+        initial: Puzzle,
+        give_up: usize,
+        mutation: G,
+        evaluator: E,
+    ) -> Self {
+        Self {
+            population: population.max(2),
+            generations,
+            elite: (population / 10).max(1),
+            tournament: 3,
+            give_up,
+            initial,
+            mutation,
+            evaluator,
+            constraints: TerminalPuzzleConstraints {},
+            cache: None,
+        }
+    }
+}
+impl<G: PuzzleGenerator, E: Evaluator, C: PuzzleConstraints> GeneticSearcher<G, E, C> {
+    pub fn with_constraints<D: PuzzleConstraints>(
+        self,
+        constraints: D,
+    ) -> GeneticSearcher<G, E, D> {
+        GeneticSearcher {
+            population: self.population,
+            generations: self.generations,
+            elite: self.elite,
+            tournament: self.tournament,
+            give_up: self.give_up,
+            initial: self.initial,
+            mutation: self.mutation,
+            evaluator: self.evaluator,
+            constraints,
+            cache: self.cache,
+        }
+    }
+    pub fn with_cache(mut self, cache: Arc<SolveCache>) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+    /// Unsolvable puzzles rank below every solvable one
+    fn evaluate(&self, puzzle: Puzzle) -> (Puzzle, Option<E::Value>) {
+        if let Some(cache) = self.cache.as_ref() {
+            let value = cache.value(&puzzle, &self.evaluator);
+            return (puzzle, value);
+        }
+        let result = puzzle.solve();
+        let value = result.ok.then(|| self.evaluator.evaluate(&puzzle, &result));
+        (puzzle, value)
+    }
+    fn select<'a>(
+        &self,
+        population: &'a [(Puzzle, Option<E::Value>)],
+        rnd: &mut impl Rng,
+    ) -> &'a Puzzle {
+        let winner = (0..self.tournament)
+            .map(|_| &population[rnd.gen_range(0..population.len())])
+            .max_by_key(|(_, value)| *value)
+            .unwrap();
+        &winner.0
+    }
+    pub fn search(&self, logger: Option<Sender<Puzzle>>, rnd: &mut SearchRng) -> Puzzle {
+        self.search_thread(logger, rnd, None, None)
+    }
+    /// Migrants from other islands replace the worst individuals.
+    /// Checkpoints keep the solvable individuals only.
+    pub fn search_thread(
+        &self,
+        logger: Option<Sender<Puzzle>>,
+        rnd: &mut SearchRng,
+        island: Option<&Island<E::Value>>,
+        mut checkpointer: Option<&mut Checkpointer<E::Value>>,
+    ) -> Puzzle {
+        let mut population = vec![];
+        let mut best = (self.initial.clone(), None);
+        let mut count = 0;
+        let mut start = 0;
+        if let Some(checkpoint) = checkpointer.as_deref_mut().and_then(Checkpointer::take) {
+            for chain in checkpoint.chains.iter() {
+                let chain = chain.to_chain(&self.initial);
+                count = chain.count;
+                population.push((chain.puzzle, Some(chain.value)));
+            }
+            if let Some(chain) = checkpoint.best {
+                let chain = chain.to_chain(&self.initial);
+                best = (chain.puzzle, Some(chain.value));
+            }
+            start = checkpoint.tries;
+            *rnd = checkpoint.rng;
+        } else {
+            population.push(self.evaluate(self.initial.clone()));
+        }
+        while population.len() < self.population {
+            population.push(self.evaluate(self.mutation.generate(&self.initial, rnd)));
+        }
+        for generation in start..self.generations {
+            if let Some(checkpointer) = checkpointer.as_deref() {
+                if generation > start && checkpointer.due(generation) {
+                    let chain = |puzzle, value| ChainJson::new(puzzle, value, count);
+                    let checkpoint = Checkpoint {
+                        version: CHECKPOINT_VERSION,
+                        tries: generation,
+                        chains: population
+                            .iter()
+                            .filter_map(|(puzzle, value)| value.map(|v| chain(puzzle, v)))
+                            .collect(),
+                        best: best.1.map(|value| chain(&best.0, value)),
+                        rng: rnd.clone(),
+                    };
+                    checkpointer.save(&checkpoint).unwrap();
+                }
+            }
+            population.sort_by_key(|(_, value)| std::cmp::Reverse(*value));
+            if let Some(island) = island {
+                let best = population[0].1.map(|value| (&population[0].0, value));
+                let migrants = island.migrate(generation, best, rnd);
+                println!("generation #{} {} migrants", generation, migrants.len());
+                let keep = population.len().saturating_sub(migrants.len());
+                population.truncate(keep.max(self.elite));
+                population.extend(migrants.into_iter().map(|(p, v)| (p, Some(v))));
+                population.sort_by_key(|(_, value)| std::cmp::Reverse(*value));
+            }
+            let (puzzle, value) = &population[0];
+            if *value > best.1 {
+                if let Some(tx) = logger.as_ref() {
+                    tx.send(puzzle.to_owned()).expect("failed to send puzzle");
+                }
+                println!("#{} updated", generation);
+                best = (puzzle.clone(), *value);
+                count = 0;
+            } else {
+                count += 1;
+                if count > self.give_up {
+                    break;
+                }
+            }
+            println!(
+                "generation #{} ({}<{}) best: {}",
+                generation,
+                count,
+                self.give_up,
+                best.1.map_or("-".to_owned(), |v| v.to_str())
+            );
+            let mut next = population[..self.elite.min(population.len())].to_vec();
+            while next.len() < self.population {
+                let a = self.select(&population, rnd);
+                let b = self.select(&population, rnd);
+                let child = crossover(a, b, &self.constraints, rnd).unwrap_or_else(|| a.clone());
+                next.push(self.evaluate(self.mutation.generate(&child, rnd)));
+            }
+            population = next;
+        }
+        best.0
+    }
+}
+impl<G: PuzzleGenerator, E: Evaluator, C: PuzzleConstraints> Searcher for GeneticSearcher<G, E, C> {
+    type Evaluator = E;
+    fn evaluator(&self) -> &E {
+        &self.evaluator
+    }
+    fn search(&self, logger: Option<Sender<Puzzle>>, rnd: &mut SearchRng) -> Puzzle {
+        GeneticSearcher::search(self, logger, rnd)
+    }
+    fn search_thread(
+        &self,
+        logger: Option<Sender<Puzzle>>,
+        rnd: &mut SearchRng,
+        island: Option<&Island<E::Value>>,
+        checkpointer: Option<&mut Checkpointer<E::Value>>,
+    ) -> Puzzle {
+        GeneticSearcher::search_thread(self, logger, rnd, island, checkpointer)
+    }
+    fn cache(&self) -> Option<&SolveCache> {
+        self.cache.as_deref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constraints::MaxPieceSizeConstraints;
+    use crate::searcher::{ShrinkStepEvaluator, SwapNPuzzleGenerator};
+    use rand::SeedableRng;
+    #[test]
+    fn test_crossover() {
+        let mut rnd = SearchRng::seed_from_u64(0);
+        let generator = SwapNPuzzleGenerator {
+            swaps: 5,
+            constraints: TerminalPuzzleConstraints {},
+        };
+        let base = Puzzle::base(3, 4, 2, None);
+        let total = |p: &Puzzle| p.pieces.iter().map(|p| p.block.count()).sum::<usize>();
+        for _ in 0..50 {
+            let a = generator.generate(&base, &mut rnd);
+            let b = generator.generate(&base, &mut rnd);
+            let Some(child) = crossover(&a, &b, &TerminalPuzzleConstraints {}, &mut rnd) else {
+                continue;
+            };
+            assert_eq!(child.pieces.len(), a.pieces.len());
+            assert_eq!(total(&child), total(&a));
+            for piece in child.pieces.iter() {
+                assert!(piece.block.is_connected());
+                assert!(piece.block.count() > 0);
+            }
+            for (i, p) in child.pieces.iter().enumerate() {
+                for q in child.pieces[i + 1..].iter() {
+                    assert!(!p.block.overlap(&q.block));
+                }
+            }
+        }
+        // children breaking the constraints of the parents are rejected
+        let constraints = MaxPieceSizeConstraints { size: 7 };
+        let generator = SwapNPuzzleGenerator {
+            swaps: 5,
+            constraints: constraints.clone(),
+        };
+        let mut children = 0;
+        for _ in 0..50 {
+            let a = generator.generate(&base, &mut rnd);
+            let b = generator.generate(&base, &mut rnd);
+            if let Some(child) = crossover(&a, &b, &constraints, &mut rnd) {
+                assert!(child.pieces.iter().all(|p| p.block.count() <= 7));
+                children += 1;
+            }
+            let impossible = MaxPieceSizeConstraints { size: 1 };
+            assert!(crossover(&a, &b, &impossible, &mut rnd).is_none());
+        }
+        assert!(children > 0);
+    }
+    #[test]
+    fn genetic_searcher() {
+        let searcher = GeneticSearcher::new(
+            6,
+            3,
+            Puzzle::base(3, 4, 1, None),
+            10,
+            SwapNPuzzleGenerator {
+                swaps: 1,
+                constraints: TerminalPuzzleConstraints {},
+            },
+            ShrinkStepEvaluator {},
+        );
+        let puzzle = searcher.search(None, &mut SearchRng::seed_from_u64(0));
+        assert!(puzzle.solve().ok);
+
+        let path = std::env::temp_dir().join(format!("genetic_{}.jsonl", std::process::id()));
+        let path = path.to_string_lossy().to_string();
+        let cache = Arc::new(SolveCache::open(&path).unwrap());
+        let searcher = searcher.with_cache(cache.clone());
+        searcher.search(None, &mut SearchRng::seed_from_u64(0));
+        std::fs::remove_file(&path).unwrap();
+        assert!(Searcher::cache(&searcher).is_some());
+        assert!(cache.len() > 0);
+    }
+}
@@ -0,0 +1,225 @@
+use serde_json::{json, Value};
+
+use crate::cells::D6;
+use crate::mesh::{Mesh, MeshOptions};
+use crate::puzzle::{Move, Puzzle, SolveResult};
+use crate::v3::{V3F, V3I};
+
+/// Same palette as the client viewer
+pub const COLORS: [u32; 8] = [
+    0xe39aac, 0xc45d9f, 0x634b7d, 0x6461c2, 0x2ba9b4, 0x93d4b5, 0xa7d129, 0x3e5f2d,
+];
+
+/// Position of every piece after each move, in units; removed pieces fly out
+pub fn piece_tracks(puzzle: &Puzzle, moves: &[Move]) -> Vec<Vec<V3I>> {
+    let mut positions = vec![Some(V3I(0, 0, 0)); puzzle.pieces.len()];
+    let mut away = vec![V3I(0, 0, 0); puzzle.pieces.len()];
+    let mut tracks = vec![vec![V3I(0, 0, 0)]; puzzle.pieces.len()];
+    for mov in moves {
+        match mov {
+            Move::Shift(pieces, v) => {
+                for &i in pieces {
+                    positions[i] = positions[i].map(|p| p + *v);
+                }
+            }
+            Move::Remove(i, d) => {
+                let Some(p) = positions[*i] else {
+                    continue;
+                };
+                // a piece the solver took out with no straight path is lifted all the same
+                let d = if *d == V3I(0, 0, 0) { D6[4] } else { *d };
+                away[*i] = p + d * (puzzle.size * 2);
+                positions[*i] = None;
+            }
+        }
+        for i in 0..puzzle.pieces.len() {
+            tracks[i].push(positions[i].unwrap_or(away[i]));
+        }
+    }
+    tracks
+}
+
+fn srgb_to_linear(c: u32) -> f64 {
+    let c = c as f64 / 255.0;
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+#[derive(Default)]
+struct Buffers {
+    bin: Vec<u8>,
+    views: Vec<Value>,
+    accessors: Vec<Value>,
+}
+impl Buffers {
+    fn push(
+        &mut self,
+        data: &[f32],
+        kind: &str,
+        count: usize,
+        min: Vec<f32>,
+        max: Vec<f32>,
+    ) -> usize {
+        self.views.push(json!({
+            "buffer": 0,
+            "byteOffset": self.bin.len(),
+            "byteLength": data.len() * 4,
+        }));
+        for x in data {
+            self.bin.extend(x.to_le_bytes());
+        }
+        self.accessors.push(json!({
+            "bufferView": self.views.len() - 1,
+            "componentType": 5126,
+            "count": count,
+            "type": kind,
+            "min": min,
+            "max": max,
+        }));
+        self.accessors.len() - 1
+    }
+    fn push_vec3(&mut self, data: &[V3F]) -> usize {
+        let inf = f64::INFINITY;
+        let (min, max) = data.iter().fold(
+            (V3F(inf, inf, inf), V3F(-inf, -inf, -inf)),
+            |(min, max), v| (min.min(v), max.max(v)),
+        );
+        let flat = data
+            .iter()
+            .flat_map(|v| [v.0 as f32, v.1 as f32, v.2 as f32])
+            .collect::<Vec<_>>();
+        let min = vec![min.0 as f32, min.1 as f32, min.2 as f32];
+        let max = vec![max.0 as f32, max.1 as f32, max.2 as f32];
+        self.push(&flat, "VEC3", data.len(), min, max)
+    }
+    fn push_scalar(&mut self, data: &[f32]) -> usize {
+        let min = data.iter().cloned().fold(f32::INFINITY, f32::min);
+        let max = data.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+        self.push(data, "SCALAR", data.len(), vec![min], vec![max])
+    }
+}
+
+/// Binary glTF with one coloured mesh per piece and the disassembly as an animation
+pub fn to_glb(puzzle: &Puzzle, result: &SolveResult, options: &MeshOptions) -> Vec<u8> {
+    let mut buffers = Buffers::default();
+    let tracks = piece_tracks(puzzle, &result.moves(puzzle));
+    let times = (0..tracks[0].len()).map(|t| t as f32).collect::<Vec<_>>();
+    let time_accessor = buffers.push_scalar(&times);
+    let (mut meshes, mut materials, mut nodes) = (vec![], vec![], vec![]);
+    let (mut channels, mut samplers) = (vec![], vec![]);
+    for (i, piece) in puzzle.pieces.iter().enumerate() {
+        let mesh = Mesh::from_piece(piece, options);
+        let positions = mesh.triangles.iter().flatten().cloned().collect::<Vec<_>>();
+        let normals = mesh
+            .triangles
+            .iter()
+            .flat_map(|[a, b, c]| [(*b - *a).cross(&(*c - *a)).normalize(); 3])
+            .collect::<Vec<_>>();
+        let position = buffers.push_vec3(&positions);
+        let normal = buffers.push_vec3(&normals);
+        let color = COLORS[i % COLORS.len()];
+        materials.push(json!({
+            "name": format!("piece_{}", i),
+            "pbrMetallicRoughness": {
+                "baseColorFactor": [
+                    srgb_to_linear(color >> 16 & 0xff),
+                    srgb_to_linear(color >> 8 & 0xff),
+                    srgb_to_linear(color & 0xff),
+                    1.0,
+                ],
+                "metallicFactor": 0.0,
+                "roughnessFactor": 0.6,
+            },
+        }));
+        meshes.push(json!({
+            "name": format!("piece_{}", i),
+            "primitives": [{
+                "attributes": {"POSITION": position, "NORMAL": normal},
+                "material": i,
+            }],
+        }));
+        nodes.push(json!({"name": format!("piece_{}", i), "mesh": i}));
+        let track = tracks[i]
+            .iter()
+            .map(|&p| V3F::from(p) * options.scale)
+            .collect::<Vec<_>>();
+        let output = buffers.push_vec3(&track);
+        samplers.push(json!({"input": time_accessor, "output": output, "interpolation": "LINEAR"}));
+        channels.push(json!({
+            "sampler": i,
+            "target": {"node": i, "path": "translation"},
+        }));
+    }
+    let children = (0..nodes.len()).collect::<Vec<_>>();
+    // meshes are in mm
+    nodes.push(json!({"name": "puzzle", "children": children, "scale": [0.001, 0.001, 0.001]}));
+    let gltf = json!({
+        "asset": {"version": "2.0", "generator": "interlocking-solver"},
+        "scene": 0,
+        "scenes": [{"nodes": [nodes.len() - 1]}],
+        "nodes": nodes,
+        "meshes": meshes,
+        "materials": materials,
+        "animations": [{"name": "disassembly", "channels": channels, "samplers": samplers}],
+        "buffers": [{"byteLength": buffers.bin.len()}],
+        "bufferViews": buffers.views,
+        "accessors": buffers.accessors,
+    });
+    let mut json_chunk = serde_json::to_vec(&gltf).unwrap();
+    json_chunk.resize(json_chunk.len().next_multiple_of(4), b' ');
+    // only f32 data, always 4 byte aligned
+    let bin_chunk = buffers.bin;
+    let length = 12 + 8 + json_chunk.len() + 8 + bin_chunk.len();
+    let mut glb = vec![];
+    glb.extend(b"glTF");
+    glb.extend(2u32.to_le_bytes());
+    glb.extend((length as u32).to_le_bytes());
+    glb.extend((json_chunk.len() as u32).to_le_bytes());
+    glb.extend(b"JSON");
+    glb.extend(json_chunk);
+    glb.extend((bin_chunk.len() as u32).to_le_bytes());
+    glb.extend(b"BIN\0");
+    glb.extend(bin_chunk);
+    glb
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::sample_puzzle;
+    #[test]
+    fn test_piece_tracks() {
+        let puzzle = sample_puzzle();
+        let result = puzzle.solve();
+        let moves = result.moves(&puzzle);
+        let tracks = piece_tracks(&puzzle, &moves);
+        assert_eq!(tracks.len(), puzzle.pieces.len());
+        for track in tracks.iter() {
+            assert_eq!(track.len(), moves.len() + 1);
+            assert_eq!(track[0], V3I(0, 0, 0));
+            // every piece ends outside of the assembled cube
+            let V3I(x, y, z) = *track.last().unwrap();
+            assert!(x.abs().max(y.abs()).max(z.abs()) >= puzzle.size as isize);
+        }
+    }
+    #[test]
+    fn test_glb() {
+        let puzzle = sample_puzzle();
+        let result = puzzle.solve();
+        let glb = to_glb(&puzzle, &result, &MeshOptions::default());
+        assert_eq!(&glb[0..4], b"glTF");
+        let length = u32::from_le_bytes(glb[8..12].try_into().unwrap()) as usize;
+        assert_eq!(length, glb.len());
+        let json_length = u32::from_le_bytes(glb[12..16].try_into().unwrap()) as usize;
+        let gltf: Value = serde_json::from_slice(&glb[20..20 + json_length]).unwrap();
+        assert_eq!(
+            gltf["meshes"].as_array().unwrap().len(),
+            puzzle.pieces.len()
+        );
+        let channels = gltf["animations"][0]["channels"].as_array().unwrap();
+        assert_eq!(channels.len(), puzzle.pieces.len());
+    }
+}
@@ -0,0 +1,198 @@
+use itertools::Itertools;
+use rand::Rng;
+
+use crate::cells::{Cells, D6};
+use crate::iters::V3Iter;
+use crate::puzzle::{Piece, Puzzle};
+use crate::searcher::{PuzzleConstraints, PuzzleGenerator};
+use crate::v3::{V3, V3I};
+
+/// Tries before giving the puzzle back unchanged, when no partition meets the constraints
+const ATTEMPTS: usize = 1000;
+
+/// Partitions the puzzle again by growing every piece from a random seed cell, a randomized
+/// BFS racing the other pieces (Voronoi-like). Gives an initial puzzle without the slabs of
+/// `Puzzle::base`, or a restart far away from the current puzzle.
+#[derive(Clone, Debug)]
+pub struct GrowPuzzleGenerator<C: PuzzleConstraints> {
+    /// Target cells of each piece, also the number of pieces; as many pieces as the puzzle
+    /// and no targets if empty. Cells left once every target is met join any piece.
+    pub sizes: Vec<usize>,
+    /// Places this many holes at random in the whole cube; `None` keeps the cells of the
+    /// puzzle (e.g. a shape)
+    pub holes: Option<usize>,
+    pub constraints: C,
+}
+impl<C: PuzzleConstraints> GrowPuzzleGenerator<C> {
+    fn region(&self, puzzle: &Puzzle, rnd: &mut impl Rng) -> Cells {
+        let Some(holes) = self.holes else {
+            let mut region = Cells::empty(puzzle.size);
+            for piece in puzzle.pieces.iter() {
+                region.or_inplace(&piece.block);
+            }
+            return region;
+        };
+        let cells = V3Iter::cube(puzzle.size).collect_vec();
+        let mut region = Cells::empty(puzzle.size);
+        for x in cells.iter() {
+            region.setv(*x, true);
+        }
+        for i in rand::seq::index::sample(rnd, cells.len(), holes.min(cells.len())) {
+            region.setv(cells[i], false);
+        }
+        region
+    }
+}
+
+struct Growth<'a> {
+    region: &'a Cells,
+    /// Cells of any piece
+    taken: Cells,
+    blocks: Vec<Cells>,
+    /// Cells next to each piece, some taken since
+    frontiers: Vec<Vec<V3>>,
+}
+impl Growth<'_> {
+    fn claim(&mut self, i: usize, x: V3) {
+        self.taken.setv(x, true);
+        self.blocks[i].setv(x, true);
+        for d in D6 {
+            let Some(n) = (V3I::from(x) + d).into_v3_in(&V3::cube(self.region.size)) else {
+                continue;
+            };
+            if self.region.getv(n) && !self.taken.getv(n) {
+                self.frontiers[i].push(n);
+            }
+        }
+    }
+}
+
+/// Splits `region` into `pieces` connected parts grown from random seeds, up to `sizes`
+/// first; `None` if some cells cannot be reached from the seeds
+pub fn partition(
+    region: &Cells,
+    sizes: &[usize],
+    pieces: usize,
+    rnd: &mut impl Rng,
+) -> Option<Vec<Cells>> {
+    let target = |i: usize| sizes.get(i).copied().unwrap_or(usize::MAX);
+    let size = region.size;
+    let cells = V3Iter::cube(size).filter(|&x| region.getv(x)).collect_vec();
+    if cells.len() < pieces {
+        return None;
+    }
+    let mut growth = Growth {
+        region,
+        taken: Cells::empty(size),
+        blocks: vec![Cells::empty(size); pieces],
+        frontiers: vec![vec![]; pieces],
+    };
+    for (i, seed) in rand::seq::index::sample(rnd, cells.len(), pieces)
+        .into_iter()
+        .enumerate()
+    {
+        growth.claim(i, cells[seed]);
+    }
+    // up to the targets first, then the rest
+    for limited in [true, false] {
+        loop {
+            let active = (0..pieces)
+                .filter(|&i| !growth.frontiers[i].is_empty())
+                .filter(|&i| !limited || growth.blocks[i].count() < target(i))
+                .collect_vec();
+            if active.is_empty() {
+                break;
+            }
+            let i = active[rnd.gen_range(0..active.len())];
+            let frontier = &mut growth.frontiers[i];
+            let x = frontier.swap_remove(rnd.gen_range(0..frontier.len()));
+            if !growth.taken.getv(x) {
+                growth.claim(i, x);
+            }
+        }
+    }
+    (growth.taken.count() == cells.len()).then_some(growth.blocks)
+}
+
+impl<C: PuzzleConstraints> PuzzleGenerator for GrowPuzzleGenerator<C> {
+    fn generate(&self, puzzle: &Puzzle, rnd: &mut impl Rng) -> Puzzle {
+        let pieces = if self.sizes.is_empty() {
+            puzzle.pieces.len()
+        } else {
+            self.sizes.len()
+        };
+        for _ in 0..ATTEMPTS {
+            let region = self.region(puzzle, rnd);
+            let Some(blocks) = partition(&region, &self.sizes, pieces, rnd) else {
+                continue;
+            };
+            if !self.constraints.is_ok(&blocks) {
+                continue;
+            }
+            let mut puzzle = puzzle.clone();
+            puzzle.pieces = blocks.iter().map(Piece::from_block).collect();
+            return puzzle;
+        }
+        println!(
+            "WARN: no partition into {:?} meets the constraints",
+            self.sizes
+        );
+        puzzle.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::puzzle_num_format::PuzzleNumFormat;
+    use crate::searcher::{MinPuzzleSizeConstraints, SearchRng, TerminalPuzzleConstraints};
+    use rand::SeedableRng;
+    #[test]
+    fn test_grow() {
+        let mut rnd = SearchRng::seed_from_u64(0);
+        let base = Puzzle::base(4, 5, 2, None);
+        let generator = GrowPuzzleGenerator {
+            sizes: vec![],
+            holes: Some(3),
+            constraints: TerminalPuzzleConstraints {},
+        };
+        let puzzle = generator.generate(&base, &mut rnd);
+        assert_eq!(puzzle.pieces.len(), 5);
+        assert!(puzzle.pieces.iter().all(|p| p.block.is_connected()));
+        let cells = puzzle.pieces.iter().map(|p| p.block.count()).sum::<usize>();
+        assert_eq!(cells, 64 - 3);
+
+        // keeps the cells of the puzzle, with as many pieces as targets
+        let generator = GrowPuzzleGenerator {
+            sizes: vec![2, 2, 2],
+            holes: None,
+            constraints: TerminalPuzzleConstraints {},
+        };
+        let small = Puzzle::base(2, 1, 2, None);
+        let puzzle = generator.generate(&small, &mut rnd);
+        assert_eq!(puzzle.pieces.len(), 3);
+        let mut region = Cells::empty(2);
+        for piece in puzzle.pieces.iter() {
+            assert!(!region.overlap(&piece.block));
+            region.or_inplace(&piece.block);
+        }
+        assert_eq!(region.count(), 6);
+        region.and_not_inplace(&small.pieces[0].block);
+        assert_eq!(region.count(), 0);
+
+        // gives the puzzle back when no partition can meet the constraints
+        let generator = GrowPuzzleGenerator {
+            sizes: vec![],
+            holes: Some(3),
+            constraints: MinPuzzleSizeConstraints {
+                size: 64,
+                next: TerminalPuzzleConstraints {},
+            },
+        };
+        let puzzle = generator.generate(&base, &mut rnd);
+        assert_eq!(
+            PuzzleNumFormat::from_puzzle(&puzzle).to_block_code(),
+            PuzzleNumFormat::from_puzzle(&base).to_block_code()
+        );
+    }
+}
@@ -0,0 +1,190 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+use itertools::Itertools;
+use rand::Rng;
+
+use crate::cells::{Cells, D6};
+use crate::iters::V3Iter;
+use crate::puzzle::{Move, Puzzle};
+use crate::puzzle_num_format::PuzzleNumFormat;
+use crate::searcher::{PuzzleConstraints, PuzzleGenerator, SwapPuzzleGenerator};
+use crate::solve_cache::SolveCache;
+use crate::v3::{V3, V3I};
+
+/// Parents remembered, the oldest one is forgotten first
+const MEMO_CAPACITY: usize = 1000;
+/// Largest `focus`: the other swaps pick any cell, in case no critical one can be moved
+const MAX_FOCUS: f64 = 0.99;
+
+/// Cells, in the assembled puzzle, where a moving piece touches a piece staying in place,
+/// for the moves before the first removal: the interlocking that makes the puzzle hard
+pub fn critical_cells(puzzle: &Puzzle, moves: &[Move]) -> Vec<V3> {
+    let size = puzzle.size;
+    let blocks = puzzle.pieces.iter().map(|p| &p.block).collect_vec();
+    // assembled cell of piece `i` at `x` when moved by `offset`
+    let cell_at = |i: usize, x: V3I, offset: V3I| {
+        (x - offset)
+            .into_v3_in(&V3::cube(size))
+            .filter(|&c| blocks[i].getv(c))
+    };
+    let mut offsets = vec![V3I(0, 0, 0); blocks.len()];
+    let mut critical = Cells::empty(size);
+    for mov in moves {
+        let Move::Shift(moving, v) = mov else {
+            break;
+        };
+        for &i in moving {
+            for c in V3Iter::cube(size).filter(|&c| blocks[i].getv(c)) {
+                let x = V3I::from(c) + offsets[i];
+                for d in D6 {
+                    let touched = (0..blocks.len())
+                        .filter(|j| !moving.contains(j))
+                        .find_map(|j| cell_at(j, x + d, offsets[j]));
+                    if let Some(other) = touched {
+                        critical.setv(c, true);
+                        critical.setv(other, true);
+                    }
+                }
+            }
+        }
+        for &i in moving {
+            offsets[i] = offsets[i] + *v;
+        }
+    }
+    V3Iter::cube(size).filter(|&x| critical.getv(x)).collect()
+}
+
+/// Swaps concentrated on the `critical_cells` of the parent, found by solving it once
+#[derive(Clone, Debug)]
+pub struct GuidedPuzzleGenerator<C: PuzzleConstraints> {
+    pub swaps: usize,
+    /// Share of swaps at critical cells, at most `MAX_FOCUS`, the others pick any cell
+    pub focus: f64,
+    pub constraints: C,
+    /// Critical cells by block code of the parent, shared by the threads
+    memo: Arc<Mutex<Memo>>,
+    /// Records the outcome of the parents solved
+    cache: Option<Arc<SolveCache>>,
+}
+#[derive(Debug, Default)]
+struct Memo {
+    cells: HashMap<String, Vec<V3>>,
+    order: VecDeque<String>,
+}
+impl<C: PuzzleConstraints> GuidedPuzzleGenerator<C> {
+    pub fn new(swaps: usize, focus: f64, constraints: C) -> Self {
+        Self {
+            swaps,
+            focus,
+            constraints,
+            memo: Arc::new(Mutex::new(Memo::default())),
+            cache: None,
+        }
+    }
+    pub fn with_cache(mut self, cache: Arc<SolveCache>) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+    fn critical(&self, puzzle: &Puzzle) -> Vec<V3> {
+        let code = PuzzleNumFormat::from_puzzle(puzzle).to_block_code();
+        if let Some(cells) = self.memo.lock().unwrap().cells.get(&code) {
+            return cells.clone();
+        }
+        let result = match self.cache.as_ref() {
+            Some(cache) => cache.solve(puzzle),
+            None => puzzle.solve(),
+        };
+        let cells = if result.ok {
+            critical_cells(puzzle, &result.moves(puzzle))
+        } else {
+            vec![]
+        };
+        let mut memo = self.memo.lock().unwrap();
+        if memo.cells.contains_key(&code) {
+            return cells;
+        }
+        if memo.order.len() >= MEMO_CAPACITY {
+            let oldest = memo.order.pop_front().unwrap();
+            memo.cells.remove(&oldest);
+        }
+        memo.cells.insert(code.clone(), cells.clone());
+        memo.order.push_back(code);
+        cells
+    }
+}
+impl<C: PuzzleConstraints> PuzzleGenerator for GuidedPuzzleGenerator<C> {
+    fn generate(&self, puzzle: &Puzzle, rnd: &mut impl Rng) -> Puzzle {
+        // without focus, the same puzzles and random sequence as `SwapNPuzzleGenerator`
+        let critical = if self.focus > 0.0 {
+            self.critical(puzzle)
+        } else {
+            vec![]
+        };
+        let swap = SwapPuzzleGenerator {
+            constraints: self.constraints.clone(),
+        };
+        let size = puzzle.size;
+        let mut puzzle = puzzle.clone();
+        for _ in 0..self.swaps {
+            puzzle = swap.generate_at(&puzzle, rnd, |rnd| {
+                if !critical.is_empty() && rnd.gen_bool(self.focus.min(MAX_FOCUS)) {
+                    return critical[rnd.gen_range(0..critical.len())];
+                }
+                V3(
+                    rnd.gen_range(0..size),
+                    rnd.gen_range(0..size),
+                    rnd.gen_range(0..size),
+                )
+            });
+        }
+        puzzle
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::searcher::{SearchRng, SwapNPuzzleGenerator, TerminalPuzzleConstraints};
+    use crate::server::sample_puzzle;
+    use rand::SeedableRng;
+    #[test]
+    fn test_critical_cells() {
+        let puzzle = sample_puzzle();
+        let result = puzzle.solve();
+        assert!(result.ok);
+        let moves = result.moves(&puzzle);
+        let critical = critical_cells(&puzzle, &moves);
+        assert!(!critical.is_empty());
+        // nothing moves before the first removal
+        let removal_first = [Move::Remove(0, V3I(1, 0, 0))];
+        assert!(critical_cells(&puzzle, &removal_first).is_empty());
+
+        let guided = GuidedPuzzleGenerator::new(2, 1.0, TerminalPuzzleConstraints {});
+        let mut rnd = SearchRng::seed_from_u64(0);
+        let child = guided.generate(&puzzle, &mut rnd);
+        let moved = puzzle
+            .pieces
+            .iter()
+            .zip(child.pieces.iter())
+            .flat_map(|(a, b)| {
+                V3Iter::cube(puzzle.size).filter(|&x| a.block.getv(x) != b.block.getv(x))
+            })
+            .collect_vec();
+        assert!(!moved.is_empty());
+        assert!(moved.iter().all(|x| critical.contains(x)));
+
+        // no focus is plain swaps
+        let unguided = GuidedPuzzleGenerator::new(3, 0.0, TerminalPuzzleConstraints {});
+        let swaps = SwapNPuzzleGenerator {
+            swaps: 3,
+            constraints: TerminalPuzzleConstraints {},
+        };
+        let a = unguided.generate(&puzzle, &mut SearchRng::seed_from_u64(1));
+        let b = swaps.generate(&puzzle, &mut SearchRng::seed_from_u64(1));
+        assert_eq!(
+            PuzzleNumFormat::from_puzzle(&a),
+            PuzzleNumFormat::from_puzzle(&b)
+        );
+    }
+}
@@ -0,0 +1,176 @@
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+use itertools::Itertools;
+use rand::Rng;
+
+use crate::puzzle::Puzzle;
+
+/// Islands receiving the migrants of an island
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Topology {
+    /// The next island only
+    Ring,
+    /// Every other island
+    Full,
+    /// One other island picked at each migration
+    Random,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Migration {
+    /// Tries (or generations) between two migrations
+    pub interval: usize,
+    pub topology: Topology,
+}
+/// `ring:INTERVAL`, `full:INTERVAL` or `random:INTERVAL`
+impl std::str::FromStr for Migration {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (topology, interval) = s
+            .split_once(':')
+            .ok_or_else(|| format!("missing interval in {}", s))?;
+        let topology = match topology {
+            "ring" => Topology::Ring,
+            "full" => Topology::Full,
+            "random" => Topology::Random,
+            _ => return Err(format!("unknown topology {}", topology)),
+        };
+        let interval = interval
+            .parse::<usize>()
+            .map_err(|e| format!("{}: {}", s, e))?;
+        Ok(Self {
+            interval: interval.max(1),
+            topology,
+        })
+    }
+}
+
+type Message<V> = Option<(Puzzle, V)>;
+
+/// One search thread of the island model, connected to every other island by a channel.
+/// Each migration sends one message to every island, empty for non-neighbours, so that
+/// all islands migrate in lockstep and seeded runs are reproducible.
+#[derive(Debug)]
+pub struct Island<V> {
+    pub id: usize,
+    migration: Migration,
+    /// From each island, `None` for this one
+    inboxes: Vec<Option<Receiver<Message<V>>>>,
+    /// To each island, `None` for this one
+    outboxes: Vec<Option<Sender<Message<V>>>>,
+}
+impl<V: Clone> Island<V> {
+    pub fn islands(n: usize, migration: Migration) -> Vec<Self> {
+        let mut islands = (0..n)
+            .map(|id| Self {
+                id,
+                migration,
+                inboxes: (0..n).map(|_| None).collect(),
+                outboxes: (0..n).map(|_| None).collect(),
+            })
+            .collect_vec();
+        for from in 0..n {
+            for to in 0..n {
+                if from != to {
+                    let (tx, rx) = channel();
+                    islands[from].outboxes[to] = Some(tx);
+                    islands[to].inboxes[from] = Some(rx);
+                }
+            }
+        }
+        islands
+    }
+    fn targets(&self, rnd: &mut impl Rng) -> Vec<usize> {
+        let n = self.outboxes.len();
+        if n < 2 {
+            return vec![];
+        }
+        match self.migration.topology {
+            Topology::Ring => vec![(self.id + 1) % n],
+            Topology::Full => (0..n).filter(|&i| i != self.id).collect(),
+            Topology::Random => {
+                let other = rnd.gen_range(1..n);
+                vec![(self.id + other) % n]
+            }
+        }
+    }
+    /// Sends `best` to the neighbours every `interval` steps, and waits for the migrants
+    /// of the same migration from the other islands
+    pub fn migrate(
+        &self,
+        step: usize,
+        best: Option<(&Puzzle, V)>,
+        rnd: &mut impl Rng,
+    ) -> Vec<(Puzzle, V)> {
+        if !step.is_multiple_of(self.migration.interval) {
+            return vec![];
+        }
+        let targets = self.targets(rnd);
+        for (i, outbox) in self.outboxes.iter().enumerate() {
+            let Some(outbox) = outbox else {
+                continue;
+            };
+            let message = best
+                .as_ref()
+                .filter(|_| targets.contains(&i))
+                .map(|(puzzle, value)| ((*puzzle).clone(), value.clone()));
+            // the other island may have stopped
+            let _ = outbox.send(message);
+        }
+        self.inboxes
+            .iter()
+            .flatten()
+            .filter_map(|inbox| inbox.recv().ok().flatten())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    /// Runs one migration on every island, each in its own thread
+    fn migrate_all(islands: Vec<Island<usize>>, step: usize, puzzle: &Puzzle) -> Vec<Vec<usize>> {
+        std::thread::scope(|scope| {
+            let handles = islands
+                .into_iter()
+                .map(|island| {
+                    scope.spawn(move || {
+                        let mut rnd = rand::thread_rng();
+                        island
+                            .migrate(step, Some((puzzle, island.id)), &mut rnd)
+                            .into_iter()
+                            .map(|(_, value)| value)
+                            .collect_vec()
+                    })
+                })
+                .collect_vec();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        })
+    }
+    #[test]
+    fn test_migration() {
+        let migration: Migration = "ring:2".parse().unwrap();
+        assert_eq!(migration.topology, Topology::Ring);
+        assert!("star:2".parse::<Migration>().is_err());
+        let puzzle = Puzzle::base(3, 4, 1, None);
+        let islands = Island::islands(3, migration);
+        // not a migration step
+        let mut rnd = rand::thread_rng();
+        assert!(islands[0]
+            .migrate(1, Some((&puzzle, 0)), &mut rnd)
+            .is_empty());
+        assert_eq!(
+            migrate_all(islands, 2, &puzzle),
+            vec![vec![2], vec![0], vec![1]]
+        );
+
+        let islands = Island::islands(3, "full:1".parse().unwrap());
+        assert_eq!(
+            migrate_all(islands, 0, &puzzle),
+            vec![vec![1, 2], vec![0, 2], vec![0, 1]]
+        );
+        let islands = Island::islands(3, "random:1".parse().unwrap());
+        let arrived = migrate_all(islands, 0, &puzzle);
+        assert_eq!(arrived.iter().map(Vec::len).sum::<usize>(), 3);
+    }
+}
@@ -1,16 +1,138 @@
-use crate::{puzzle::*, searcher::*, server::PuzzleJson};
-use mongodb::bson::oid::ObjectId;
+use crate::{
+    archive::{Archive, ArchiveRecord, SolveStats},
+    checkpoint::{Checkpointer, RunJson},
+    island::{Island, Migration},
+    pareto::ParetoFront,
+    puzzle::*,
+    puzzle_num_format::PuzzleNumFormat,
+    searcher::*,
+    server::{FrontEntryJson, FrontJson, PuzzleJson},
+    solve_cache::SolveCache,
+};
+use mongodb::bson::{doc, oid::ObjectId};
+use rand::SeedableRng;
 use serde::{Deserialize, Serialize};
+use std::sync::Arc;
 
-pub struct Launcher<G: PuzzleGenerator, E: Evaluator> {
-    searcher: PuzzleSearcher<G, E>,
+/// Tries between two checkpoints of a search thread in `Launcher::for_run`
+const CHECKPOINT_INTERVAL: usize = 1000;
+
+pub struct Launcher<S: Searcher> {
+    searcher: S,
     parallel: usize,
     write_steps: bool,
+    /// Threads run as islands exchanging puzzles, independent if `None`
+    migration: Option<Migration>,
+    /// Thread `i` draws from stream `i` of this seed
+    seed: u64,
+    /// Directory and interval of the per-thread checkpoints, and whether to load them
+    checkpoint: Option<(String, usize, bool)>,
+    /// Run spec the searcher was built from
+    spec: Option<String>,
 }
 
+/// Command line of `main`, for `Launcher::for_run`
+#[derive(Debug, Clone, Default)]
+pub struct RunArgs {
+    /// Recorded for `resume`, the replayed ones when resuming
+    pub args: Vec<String>,
+    /// Continues from the checkpoints of the run
+    pub resume: bool,
+    /// Overrides the seeds of the run configs and the resumed runs
+    pub seed: Option<u64>,
+}
+impl RunArgs {
+    /// `seed` is the `SEED` environment variable, if set
+    pub fn parse(args: Vec<String>, resume: bool, seed: Option<&str>) -> Result<Self, String> {
+        let seed = seed
+            .map(|seed| seed.parse().map_err(|e| format!("SEED {}: {}", seed, e)))
+            .transpose()?;
+        Ok(Self { args, resume, seed })
+    }
+    /// Seed of `run`: the one of the args if set, else the one of the resumed run, else
+    /// `seed`, else a random one
+    pub fn run_seed(&self, run: &str, seed: Option<u64>) -> Result<u64, String> {
+        match self.seed {
+            Some(seed) => Ok(seed),
+            None if self.resume => Ok(RunJson::load(&format!("checkpoints/{}", run))?.seed),
+            None => Ok(seed.unwrap_or_else(rand::random)),
+        }
+    }
+}
+
+type Value<S> = <<S as Searcher>::Evaluator as Evaluator>::Value;
+
 pub trait PuzzleWriter {
     async fn write<V: EvalValue>(&self, puzzle: &Puzzle, result: &SolveResult, value: V);
-    async fn write_config(&self, log: &str);
+    async fn write_config(&self, log: &str, seed: u64);
+    /// Replaces the Pareto front of the run
+    async fn write_front(&self, entries: Vec<FrontEntryJson>);
+    /// Pareto front written by an earlier launch of the run, empty if none
+    async fn load_front(&self) -> Vec<FrontEntryJson>;
+}
+
+/// Entries of the front file at `path`, empty if there is none
+fn read_front(path: &str) -> Vec<FrontEntryJson> {
+    let Ok(text) = std::fs::read_to_string(path) else {
+        return vec![];
+    };
+    match serde_json::from_str::<FrontJson>(&text) {
+        Ok(front) => front.entries,
+        Err(e) => {
+            println!("WARN: {}: {}", path, e);
+            vec![]
+        }
+    }
+}
+
+/// `entries` with a value of type `V`, to start the front of the launch from
+fn front_of<V: EvalValue>(entries: Vec<FrontEntryJson>) -> ParetoFront<PuzzleJson, V> {
+    let mut front = ParetoFront::default();
+    for entry in entries {
+        match entry.objectives.map(serde_json::from_value) {
+            Some(Ok(value)) => {
+                front.insert(entry.puzzle, value);
+            }
+            _ => println!("WARN: front entry {} has no value, dropped", entry.value),
+        }
+    }
+    front
+}
+
+/// Bumped on incompatible changes of `PuzzleSidecarJson`
+pub const SIDECAR_VERSION: u32 = 1;
+
+/// Written next to each `.pcad` by `PuzzleFileWriter`
+#[derive(Serialize, Deserialize)]
+pub struct PuzzleSidecarJson {
+    pub version: u32,
+    pub value: String,
+    #[serde(flatten)]
+    pub puzzle: PuzzleJson,
+}
+
+/// Reads every sidecar in `dir`, sorted by file name
+pub fn load_sidecars(dir: &str) -> Result<Vec<PuzzleSidecarJson>, String> {
+    let mut paths = std::fs::read_dir(dir)
+        .map_err(|e| format!("{}: {}", dir, e))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .collect::<Vec<_>>();
+    paths.sort();
+    let mut sidecars = vec![];
+    for path in paths {
+        let text = std::fs::read_to_string(&path).map_err(|e| format!("{:?}: {}", path, e))?;
+        let sidecar: PuzzleSidecarJson =
+            serde_json::from_str(&text).map_err(|e| format!("{:?}: {}", path, e))?;
+        if sidecar.version > SIDECAR_VERSION {
+            return Err(format!(
+                "{:?}: unsupported version {}",
+                path, sidecar.version
+            ));
+        }
+        sidecars.push(sidecar);
+    }
+    Ok(sidecars)
 }
 
 #[derive(Debug)]
@@ -28,16 +150,76 @@ impl PuzzleWriter for PuzzleFileWriter {
     async fn write<V: EvalValue>(&self, puzzle: &Puzzle, result: &SolveResult, value: V) {
         let date = chrono::Local::now();
         let date_path = date.format("%Y%m%dT%H%M%S").to_string();
-        let path = format!("{}/{}_step_{}.pcad", self.dir, date_path, value.to_path());
+        let name = format!("{}_step_{}", date_path, value.to_path());
+        let path = format!("{}/{}.pcad", self.dir, name);
         let moves = result.moves(puzzle);
         let shrink_moves = result.shrink_move(&moves);
         let pcad = format!("{}\n\n//{:?}", puzzle.to_pcad(), shrink_moves);
         println!("write to {}", path);
         std::fs::write(path, pcad).unwrap();
+
+        let mut puzzle_json = PuzzleJson::from_result(puzzle, result);
+        puzzle_json.name = name.clone();
+        puzzle_json.run = self.dir.clone();
+        puzzle_json.date = date.to_rfc3339();
+        let sidecar = PuzzleSidecarJson {
+            version: SIDECAR_VERSION,
+            value: value.to_str(),
+            puzzle: puzzle_json,
+        };
+        let path = format!("{}/{}.json", self.dir, name);
+        std::fs::write(path, serde_json::to_string_pretty(&sidecar).unwrap()).unwrap();
     }
-    async fn write_config(&self, log: &str) {
+    async fn write_config(&self, log: &str, seed: u64) {
         let path = format!("{}/config.log", self.dir);
-        std::fs::write(path, log).unwrap();
+        std::fs::write(path, format!("seed: {}\n{}", seed, log)).unwrap();
+    }
+    async fn write_front(&self, entries: Vec<FrontEntryJson>) {
+        let path = format!("{}/front.json", self.dir);
+        let front = FrontJson::new(&self.dir, entries);
+        std::fs::write(path, serde_json::to_string_pretty(&front).unwrap()).unwrap();
+    }
+    async fn load_front(&self) -> Vec<FrontEntryJson> {
+        read_front(&format!("{}/front.json", self.dir))
+    }
+}
+
+pub struct ArchiveWriter {
+    path: String,
+    archive: std::sync::Mutex<Archive>,
+}
+impl ArchiveWriter {
+    pub fn new(path: &str) -> Self {
+        let archive = Archive::open(path).unwrap();
+        println!("INFO: {} puzzles in {}", archive.len(), path);
+        Self {
+            path: path.to_owned(),
+            archive: std::sync::Mutex::new(archive),
+        }
+    }
+}
+impl PuzzleWriter for ArchiveWriter {
+    async fn write<V: EvalValue>(&self, puzzle: &Puzzle, result: &SolveResult, value: V) {
+        let record = ArchiveRecord {
+            code: PuzzleNumFormat::from_puzzle(puzzle),
+            value: value.to_path(),
+            stats: SolveStats::from_result(puzzle, result),
+        };
+        if self.archive.lock().unwrap().append(record).unwrap() {
+            println!("written to archive");
+        }
+    }
+    async fn write_config(&self, log: &str, seed: u64) {
+        let path = format!("{}.config.log", self.path);
+        std::fs::write(path, format!("seed: {}\n{}", seed, log)).unwrap();
+    }
+    async fn write_front(&self, entries: Vec<FrontEntryJson>) {
+        let path = format!("{}.front.json", self.path);
+        let front = FrontJson::new(&self.path, entries);
+        std::fs::write(path, serde_json::to_string_pretty(&front).unwrap()).unwrap();
+    }
+    async fn load_front(&self) -> Vec<FrontEntryJson> {
+        read_front(&format!("{}.front.json", self.path))
     }
 }
 
@@ -45,15 +227,25 @@ impl PuzzleWriter for PuzzleFileWriter {
 pub struct DBWriter {
     client: mongodb::Client,
     run: String,
+    /// Normalize mirror images to the same code
+    mirror: bool,
+    cache: Option<Arc<SolveCache>>,
 }
 impl DBWriter {
-    pub async fn new(uri: &str, run: &str) -> Self {
+    pub async fn new(uri: &str, run: &str, mirror: bool) -> Self {
         let client = mongodb::Client::with_uri_str(uri).await.unwrap();
         Self {
             client,
             run: run.to_owned(),
+            mirror,
+            cache: None,
         }
     }
+    /// Stores new outcomes of `cache` in the `solve_cache` collection on every write
+    pub fn with_cache(mut self, cache: Arc<SolveCache>) -> Self {
+        self.cache = Some(cache);
+        self
+    }
 }
 
 #[derive(Serialize, Deserialize)]
@@ -63,10 +255,14 @@ struct RunMetaJson {
     run: String,
     date: String,
     config: String,
+    /// Missing in runs before seeding
+    #[serde(default)]
+    seed: Option<u64>,
 }
 impl PuzzleWriter for DBWriter {
     async fn write<V: EvalValue>(&self, puzzle: &Puzzle, _result: &SolveResult, value: V) {
-        let mut puzzle_json = PuzzleJson::normalized_from_puzzle(puzzle);
+        let mut puzzle_json =
+            PuzzleJson::normalized_from_puzzle(puzzle, self.mirror, self.cache.as_deref());
         puzzle_json.run = self.run.clone();
         let date = chrono::Local::now();
         let date_path = date.format("%Y%m%dT%H%M%S").to_string();
@@ -77,52 +273,281 @@ impl PuzzleWriter for DBWriter {
         let db = self.client.database("puzzle");
         let collection = db.collection("generated");
         collection.insert_one(puzzle_json, None).await.unwrap();
+        if let Some(cache) = self.cache.as_ref() {
+            cache.flush_db(&db).await.unwrap();
+        }
         println!("written to db")
     }
-    async fn write_config(&self, log: &str) {
+    async fn write_config(&self, log: &str, seed: u64) {
         let meta = RunMetaJson {
             id: None,
             run: self.run.clone(),
             date: chrono::Local::now().to_rfc3339(),
             config: log.to_owned(),
+            seed: Some(seed),
         };
         let db = self.client.database("puzzle");
         let collection = db.collection("run_meta");
         collection.insert_one(meta, None).await.unwrap();
     }
+    async fn write_front(&self, entries: Vec<FrontEntryJson>) {
+        let front = FrontJson::new(&self.run, entries);
+        let collection = self.client.database("puzzle").collection("front");
+        let options = mongodb::options::ReplaceOptions::builder()
+            .upsert(true)
+            .build();
+        collection
+            .replace_one(doc! { "run": &self.run }, front, options)
+            .await
+            .unwrap();
+    }
+    async fn load_front(&self) -> Vec<FrontEntryJson> {
+        let collection = self
+            .client
+            .database("puzzle")
+            .collection::<FrontJson>("front");
+        let front = collection
+            .find_one(doc! { "run": &self.run }, None)
+            .await
+            .unwrap();
+        front.map_or(vec![], |front| front.entries)
+    }
 }
 
-impl<G: PuzzleGenerator, E: Evaluator> Launcher<G, E> {
-    pub fn new(searcher: PuzzleSearcher<G, E>, parallel: usize, write_steps: bool) -> Self {
+impl<S: Searcher> Launcher<S> {
+    pub fn new(searcher: S, parallel: usize, write_steps: bool) -> Self {
         Self {
             searcher,
             parallel,
             write_steps,
+            migration: None,
+            seed: rand::random(),
+            checkpoint: None,
+            spec: None,
         }
     }
+    /// Saves every thread every `interval` tries to `dir`; with `resume` the threads
+    /// first continue from the checkpoints already there
+    pub fn with_checkpoint(mut self, dir: &str, interval: usize, resume: bool) -> Self {
+        self.checkpoint = Some((dir.to_owned(), interval, resume));
+        self
+    }
+    /// Checkpointed to `checkpoints/<run>` and continued by the `resume <run>` command.
+    /// The seed is `RunArgs::run_seed`.
+    pub fn for_run(
+        searcher: S,
+        parallel: usize,
+        run: &str,
+        seed: Option<u64>,
+        args: &RunArgs,
+    ) -> Result<Self, String> {
+        let dir = format!("checkpoints/{}", run);
+        let mut launcher = Self::new(searcher, parallel, false).with_checkpoint(
+            &dir,
+            CHECKPOINT_INTERVAL,
+            args.resume,
+        );
+        launcher = launcher.with_seed(args.run_seed(run, seed)?);
+        if !args.resume {
+            let run = RunJson {
+                args: args.args.clone(),
+                seed: launcher.seed,
+            };
+            run.save(&dir)?;
+        }
+        Ok(launcher)
+    }
+    /// Recorded by `write_config` in front of the searcher
+    pub fn with_spec(mut self, spec: &str) -> Self {
+        self.spec = Some(spec.to_owned());
+        self
+    }
+    /// Same seed and `parallel` give the same puzzles on each thread. The threads share one
+    /// channel, so the writing order, and which of two equal values stays in the front, may
+    /// differ between runs.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+    fn rng(&self, thread: usize) -> SearchRng {
+        let mut rnd = SearchRng::seed_from_u64(self.seed);
+        rnd.set_stream(thread as u64);
+        rnd
+    }
+    pub fn with_migration(mut self, migration: Migration) -> Self {
+        self.migration = Some(migration);
+        self
+    }
 }
 
-impl<G: PuzzleGenerator + 'static, E: Evaluator + 'static> Launcher<G, E> {
+impl<S: Searcher + 'static> Launcher<S> {
     pub async fn launch<W: PuzzleWriter>(&self, writer: W) -> Result<(), String> {
-        writer.write_config(&format!("{:#?}", self.searcher)).await;
+        println!("INFO: seed {}", self.seed);
+        writer
+            .write_config(
+                &format!(
+                    "{}{:#?}\n{:#?}",
+                    self.spec
+                        .as_ref()
+                        .map_or(String::new(), |spec| spec.clone() + "\n"),
+                    self.searcher,
+                    self.migration
+                ),
+                self.seed,
+            )
+            .await;
         let (tx, rx) = std::sync::mpsc::channel();
-        {
-            for _ in 0..self.parallel {
-                let tx = tx.clone();
-                let searcher = self.searcher.clone();
-                std::thread::spawn(move || loop {
-                    let puzzle = searcher.search(Some(tx.clone()));
-                    tx.send(puzzle).unwrap();
-                });
-            }
+        let mut islands = match self.migration {
+            Some(migration) => Island::islands(self.parallel, migration)
+                .into_iter()
+                .map(Some)
+                .collect(),
+            None => (0..self.parallel).map(|_| None).collect::<Vec<_>>(),
+        };
+        for (i, island) in islands.drain(..).enumerate() {
+            let mut checkpointer = match self.checkpoint.as_ref() {
+                Some((dir, interval, resume)) => {
+                    Some(Checkpointer::open(dir, i, *interval, *resume)?)
+                }
+                None => None,
+            };
+            let tx = tx.clone();
+            let searcher = self.searcher.clone();
+            let mut rnd = self.rng(i);
+            std::thread::spawn(move || loop {
+                let logger = Some(tx.clone());
+                let puzzle = match (island.as_ref(), checkpointer.as_mut()) {
+                    (None, None) => searcher.search(logger, &mut rnd),
+                    (island, checkpointer) => {
+                        searcher.search_thread(logger, &mut rnd, island, checkpointer)
+                    }
+                };
+                tx.send(puzzle).unwrap();
+            });
         }
+        // the front of the run so far, which `write_front` replaces
+        let mut front = if Value::<S>::PARETO {
+            front_of(writer.load_front().await)
+        } else {
+            ParetoFront::default()
+        };
         for puzzle in rx {
-            let result = puzzle.solve();
+            let result = match self.searcher.cache() {
+                Some(cache) => {
+                    if cache.get(&puzzle).is_some_and(|outcome| !outcome.solvable) {
+                        continue;
+                    }
+                    cache.solve(&puzzle)
+                }
+                None => puzzle.solve(),
+            };
             if result.ok {
-                let value = self.searcher.evaluator.evaluate(&puzzle, &result);
+                let value = self.searcher.evaluator().evaluate(&puzzle, &result);
                 writer.write(&puzzle, &result, value).await;
+                if Value::<S>::PARETO
+                    && front.insert(PuzzleJson::from_result(&puzzle, &result), value)
+                {
+                    let entries = front
+                        .entries
+                        .iter()
+                        .map(|(puzzle, value)| FrontEntryJson {
+                            value: value.to_str(),
+                            objectives: serde_json::to_value(value).ok(),
+                            puzzle: puzzle.clone(),
+                        })
+                        .collect();
+                    writer.write_front(entries).await;
+                }
             }
         }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pareto::ParetoValue;
+    use crate::server::sample_puzzle;
+    #[test]
+    fn test_run_args() {
+        let args = vec!["solver".to_owned(), "run".to_owned()];
+        let parsed = RunArgs::parse(args.clone(), false, Some("42")).unwrap();
+        assert_eq!(
+            (parsed.args.clone(), parsed.resume, parsed.seed),
+            (args.clone(), false, Some(42))
+        );
+        assert_eq!(RunArgs::parse(args.clone(), true, None).unwrap().seed, None);
+        assert!(RunArgs::parse(args.clone(), false, Some("forty-two")).is_err());
+        // SEED before the seed of the spec
+        assert_eq!(parsed.run_seed("x", Some(7)), Ok(42));
+        let unseeded = RunArgs::parse(args.clone(), false, None).unwrap();
+        assert_eq!(unseeded.run_seed("x", Some(7)), Ok(7));
+        // the seed of a resumed run comes from its run.json
+        let resumed = RunArgs::parse(args, true, None).unwrap();
+        assert!(resumed.run_seed("no_such_run", Some(7)).is_err());
+    }
+    #[tokio::test]
+    async fn test_sidecar_roundtrip() {
+        let dir = std::env::temp_dir().join(format!("sidecar_{}", std::process::id()));
+        let dir = dir.to_string_lossy().to_string();
+        let writer = PuzzleFileWriter::new(dir.clone());
+        let puzzle = sample_puzzle();
+        let result = puzzle.solve();
+        let value = ShrinkStepEvaluator {}.evaluate(&puzzle, &result);
+        writer.write(&puzzle, &result, value).await;
+        let sidecars = load_sidecars(&dir).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(sidecars.len(), 1);
+        let sidecar = &sidecars[0];
+        assert_eq!(sidecar.version, SIDECAR_VERSION);
+        assert_eq!(sidecar.value, value.to_str());
+        assert_eq!(
+            sidecar.puzzle.code,
+            PuzzleNumFormat::from_puzzle(&puzzle).to_block_code()
+        );
+        assert_eq!(
+            sidecar.puzzle.solution.moves.len(),
+            result.moves(&puzzle).len()
+        );
+    }
+    #[tokio::test]
+    async fn test_front_file() {
+        let dir = std::env::temp_dir().join(format!("front_{}", std::process::id()));
+        let dir = dir.to_string_lossy().to_string();
+        let writer = PuzzleFileWriter::new(dir.clone());
+        let puzzle = sample_puzzle();
+        let result = puzzle.solve();
+        assert!(writer.load_front().await.is_empty());
+        let value = ParetoValue {
+            level: 1,
+            pieces: 3,
+            dead_ends: 0,
+            unprintable: 0,
+        };
+        let entry = FrontEntryJson {
+            value: value.to_str(),
+            objectives: serde_json::to_value(value).ok(),
+            puzzle: PuzzleJson::from_result(&puzzle, &result),
+        };
+        let old = FrontEntryJson {
+            value: "level=2".to_owned(),
+            objectives: None,
+            puzzle: PuzzleJson::from_result(&puzzle, &result),
+        };
+        writer.write_front(vec![entry, old]).await;
+        let text = std::fs::read_to_string(format!("{}/front.json", dir));
+        let loaded = writer.load_front().await;
+        std::fs::remove_dir_all(&dir).unwrap();
+        let front: FrontJson = serde_json::from_str(&text.unwrap()).unwrap();
+        assert_eq!(front.run, dir);
+        assert_eq!(front.entries.len(), 2);
+        assert_eq!(front.entries[0].value, value.to_str());
+        // the next launch starts from the entries with a value
+        let mut front = front_of::<ParetoValue>(loaded);
+        assert_eq!(front.entries.len(), 1);
+        assert_eq!(front.entries[0].1, value);
+        let worse = ParetoValue { level: 0, ..value };
+        assert!(!front.insert(PuzzleJson::from_result(&puzzle, &result), worse));
+    }
+}
@@ -1,54 +1,141 @@
+mod archive;
 mod cells;
+mod checkpoint;
+mod constraints;
+mod features;
 mod gen_all_puzzles;
+mod genetic;
+mod gltf;
+mod grow;
+mod guided;
+mod island;
 mod iters;
 mod launcher;
+mod mesh;
+mod mutation;
+mod pareto;
+mod printability;
 mod puzzle;
 mod puzzle_num_format;
 mod searcher;
 mod server;
+mod solve_cache;
+mod spec;
+mod stl;
+mod strategy;
+mod svg;
 mod v3;
+mod vox;
+mod voxelize;
+mod weighted;
 
 use std::env;
 
 use axum::{routing::get, Router};
-use launcher::Launcher;
+use island::Migration;
+use launcher::{Launcher, RunArgs};
 use mongodb::bson::doc;
 use puzzle::Puzzle;
 use puzzle_num_format::PuzzleNumFormat;
 use searcher::*;
+use strategy::{Beam, Restart, Tabu};
 
 use crate::server::{sample_puzzle, PuzzleJson};
 
-async fn launch_generate_db() {
+/// Last component of `path` without its extension
+fn run_name(path: &str) -> String {
+    std::path::Path::new(path)
+        .file_stem()
+        .unwrap()
+        .to_string_lossy()
+        .to_string()
+}
+
+async fn launch_file<S: Searcher + 'static>(
+    searcher: S,
+    parallel: usize,
+    migration: Option<Migration>,
+    dir: &str,
+    args: &RunArgs,
+) {
+    let mut launcher = Launcher::for_run(searcher, parallel, &run_name(dir), None, args).unwrap();
+    if let Some(migration) = migration {
+        launcher = launcher.with_migration(migration);
+    }
+    let writer = launcher::PuzzleFileWriter::new(dir.to_string());
+    launcher.launch(writer).await.unwrap();
+}
+
+/// `strategy` is `restart`, `tabu`, `beam` or an annealing schedule, `hill` is the one of
+/// `specs/generate_file.json`
+fn file_strategy(strategy: &str) -> spec::StrategySpec {
+    match strategy {
+        "restart" => spec::StrategySpec::Restart(Restart {
+            give_up: 10000,
+            restarts: 10,
+        }),
+        "tabu" => spec::StrategySpec::Tabu(Tabu {
+            neighbors: 4,
+            tenure: 50,
+            give_up: 100000,
+        }),
+        "beam" => spec::StrategySpec::Beam(Beam {
+            width: 4,
+            branching: 2,
+            give_up: 10000,
+        }),
+        schedule => spec::StrategySpec::Annealing {
+            schedule: schedule.to_owned(),
+            give_up: 100000,
+        },
+    }
+}
+
+/// One island per thread with `migration`, a single population otherwise
+async fn launch_genetic(population: usize, migration: Option<Migration>, args: &RunArgs) {
     let constraints = MinPuzzleSizeConstraints {
-        size: 4,
+        size: 2,
         next: TerminalPuzzleConstraints {},
     };
-    let searcher = PuzzleSearcher::new(
-        1000000,
-        1,
+    let searcher = genetic::GeneticSearcher::new(
+        population,
+        100000,
         Puzzle::base(4, 5, 2, Some(1000)),
-        50000,
+        1000,
         SwapNPuzzleGenerator {
-            swaps: 3,
-            constraints,
+            swaps: 1,
+            constraints: constraints.clone(),
         },
         ShrinkStepEvaluator {},
-    );
-    let launcher = Launcher::new(searcher, 4, false);
-    let writer = launcher::DBWriter::new(&get_mongo_uri(), &"5_piece_2_hole_50k_try").await;
-    launcher.launch(writer).await.unwrap();
+    )
+    .with_constraints(constraints);
+    let parallel = if migration.is_some() { 4 } else { 1 };
+    launch_file(searcher, parallel, migration, "puzzles/genetic_4x4_5", args).await;
 }
 
-async fn launch_generate_file() {
-    let constraints = MinPuzzleSizeConstraints {
-        size: 2,
-        next: TerminalPuzzleConstraints {},
+fn load_shape(path: &str, size: usize) -> cells::Cells {
+    let mesh = stl::read(&std::fs::read(path).unwrap()).unwrap();
+    let shape = voxelize::voxelize(&mesh, size);
+    println!("shape {} cells\n{}", shape.count(), shape.to_str());
+    if !shape.is_connected() {
+        println!("WARN: shape is not connected");
+    }
+    shape
+}
+
+async fn launch_generate_shape(path: &str, size: usize, pieces: usize, args: &RunArgs) {
+    let shape = load_shape(path, size);
+    let constraints = ShapePuzzleConstraints {
+        shape: shape.clone(),
+        next: MinPuzzleSizeConstraints {
+            size: 2,
+            next: TerminalPuzzleConstraints {},
+        },
     };
     let searcher = PuzzleSearcher::new(
         1000000,
         1,
-        Puzzle::base(4, 5, 2, Some(1000)),
+        Puzzle::base_in(&shape, pieces, Some(1000)),
         100000,
         SwapNPuzzleGenerator {
             swaps: 3,
@@ -56,9 +143,9 @@ async fn launch_generate_file() {
         },
         ShrinkStepEvaluator {},
     );
-    let launcher = Launcher::new(searcher, 4, false);
-    let writer =
-        launcher::PuzzleFileWriter::new("puzzles/puzzle_20240122_4x4_5_swap3ok".to_string());
+    let run = format!("shape_{}_{}", run_name(path), size);
+    let launcher = Launcher::for_run(searcher, 4, &run, None, args).unwrap();
+    let writer = launcher::PuzzleFileWriter::new(format!("puzzles/{}", run));
     launcher.launch(writer).await.unwrap();
 }
 
@@ -74,6 +161,40 @@ fn launch_gen_all_puzzles() {
     println!("{}", writer.codes.len());
 }
 
+fn gen_all_archive(size: usize, piece: usize, path: &str) {
+    let mut archive = archive::Archive::open(path).unwrap();
+    gen_all_puzzles::GenAllPuzzles {
+        size,
+        piece,
+        holes: 0,
+    }
+    .generate(&mut archive);
+    println!("INFO: {} puzzles in {}", archive.len(), path);
+}
+
+fn query_archive(path: &str, code: Option<&String>) {
+    if let Some(code) = code {
+        let mut archive = archive::Archive::open(path).unwrap();
+        match archive
+            .get(&PuzzleNumFormat::from_block_code(code))
+            .unwrap()
+        {
+            Some(record) => println!("{:?}", record),
+            None => println!("INFO: not found"),
+        }
+        return;
+    }
+    for entry in archive::read_archive(path).unwrap() {
+        let (_, record) = entry.unwrap();
+        println!(
+            "{}\t{}\t{:?}",
+            record.code.to_block_code(),
+            record.value,
+            record.stats
+        );
+    }
+}
+
 fn solve_sample_puzzle() {
     let puzzle = sample_puzzle();
     assert!(puzzle.check_puzzle());
@@ -104,6 +225,117 @@ async fn dump_puzzle(name: &str) {
     fs::write(path, pcad).unwrap();
 }
 
+fn export_stl(code: &str, dir: &str, ascii: bool) {
+    use std::fs;
+    let puzzle = PuzzleNumFormat::from_block_code(code).to_puzzle();
+    let options = mesh::MeshOptions::default();
+    fs::create_dir_all(dir).unwrap();
+    let write = |name: &str, mesh: &mesh::Mesh| {
+        if !mesh.is_watertight() {
+            println!("WARN: {} is not watertight", name);
+        }
+        let path = format!("{}/{}.stl", dir, name);
+        println!("write {}", path);
+        if ascii {
+            fs::write(path, stl::to_ascii(mesh, name)).unwrap();
+        } else {
+            fs::write(path, stl::to_binary(mesh)).unwrap();
+        }
+    };
+    for (i, piece) in puzzle.pieces.iter().enumerate() {
+        write(
+            &format!("piece_{}", i),
+            &mesh::Mesh::from_piece(piece, &options),
+        );
+    }
+    write("plate", &mesh::Mesh::plate(&puzzle, &options));
+}
+
+fn export_gltf(code: &str, path: &str) {
+    let puzzle = PuzzleNumFormat::from_block_code(code).to_puzzle();
+    let result = puzzle.solve();
+    if !result.ok {
+        println!("ERROR: {} has no solution", code);
+        return;
+    }
+    let options = mesh::MeshOptions::default();
+    println!("write {}", path);
+    std::fs::write(path, gltf::to_glb(&puzzle, &result, &options)).unwrap();
+}
+
+fn export_sheet(code: &str, path: &str) {
+    let puzzle = PuzzleNumFormat::from_block_code(code).to_puzzle();
+    let result = puzzle.solve();
+    if !result.ok {
+        println!("ERROR: {} has no solution", code);
+        return;
+    }
+    println!("write {}", path);
+    std::fs::write(path, svg::instruction_sheet(&puzzle, &result, code)).unwrap();
+}
+
+fn import_vox(path: &str) {
+    let bytes = std::fs::read(path).unwrap();
+    match vox::from_vox(&bytes) {
+        Ok(format) => {
+            let puzzle = format.to_puzzle();
+            if !puzzle.check_puzzle() {
+                println!("ERROR: {} is not a valid puzzle", path);
+            }
+            println!("{}", format.to_block_code());
+        }
+        Err(e) => println!("ERROR: {}", e),
+    }
+}
+
+fn export_vox(code: &str, path: &str) {
+    let puzzle = PuzzleNumFormat::from_block_code(code).to_puzzle();
+    match vox::to_vox(&puzzle) {
+        Ok(bytes) => {
+            println!("write {}", path);
+            std::fs::write(path, bytes).unwrap();
+        }
+        Err(e) => println!("ERROR: {}", e),
+    }
+}
+
+/// Features of the sidecars in the directory `source`, or of the run `source` in the DB
+async fn export_features(source: &str, path: &str) {
+    let puzzles = if std::path::Path::new(source).is_dir() {
+        launcher::load_sidecars(source)
+            .unwrap()
+            .into_iter()
+            .map(|sidecar| sidecar.puzzle)
+            .collect::<Vec<_>>()
+    } else {
+        let client = mongodb::Client::with_uri_str(&get_mongo_uri())
+            .await
+            .unwrap();
+        let generated = client.database("puzzle").collection("generated");
+        let puzzles = generated.find(doc! {"run": source}, None).await.unwrap();
+        use futures::stream::TryStreamExt;
+        puzzles.try_collect::<Vec<PuzzleJson>>().await.unwrap()
+    };
+    match features::to_csv(&puzzles) {
+        Ok(csv) => {
+            println!("write {} puzzles to {}", puzzles.len(), path);
+            std::fs::write(path, csv).unwrap();
+        }
+        Err(e) => println!("ERROR: {}", e),
+    }
+}
+
+fn list_sidecars(dir: &str) {
+    let sidecars = launcher::load_sidecars(dir).unwrap();
+    for sidecar in sidecars.iter() {
+        println!(
+            "{}\t{}\t{}",
+            sidecar.puzzle.name, sidecar.value, sidecar.puzzle.code
+        );
+    }
+    println!("INFO: {} puzzles", sidecars.len());
+}
+
 pub fn get_mongo_uri() -> String {
     let mongo_uri = env::var("MONGO_URI").unwrap();
     assert!(
@@ -120,6 +352,7 @@ async fn launch_server() {
     let app = Router::new()
         .route("/api/hello", get(hello))
         .route("/api/puzzles", get(server::puzzles))
+        .route("/api/front", get(server::front))
         .with_state(client);
     let listener = tokio::net::TcpListener::bind("0.0.0.0:13013")
         .await
@@ -134,16 +367,124 @@ async fn hello() -> &'static str {
 
 #[tokio::main]
 async fn main() {
-    let cmd = env::args().collect::<Vec<_>>();
+    let mut cmd = env::args().collect::<Vec<_>>();
+    let resume = cmd.get(1).is_some_and(|c| c == "resume");
+    if resume {
+        let run = cmd.get(2).unwrap();
+        cmd = checkpoint::RunJson::load(&format!("checkpoints/{}", run))
+            .unwrap()
+            .args;
+        println!("INFO: resume {}", cmd[1..].join(" "));
+    }
+    let args = match RunArgs::parse(cmd.clone(), resume, env::var("SEED").ok().as_deref()) {
+        Ok(args) => args,
+        Err(e) => {
+            println!("ERROR: {}", e);
+            return;
+        }
+    };
     match &cmd.get(1).unwrap_or(&"".to_owned())[..] {
-        "generate" => launch_generate_db().await,
-        "generate_file" => launch_generate_file().await,
+        "generate" => {
+            spec::RunSpec::load("specs/generate_db.json")
+                .unwrap()
+                .launch(&args)
+                .await
+                .unwrap();
+        }
+        "run" => {
+            let path = cmd.get(2).unwrap();
+            spec::RunSpec::load(path)
+                .unwrap()
+                .launch(&args)
+                .await
+                .unwrap();
+        }
+        "generate_file" => {
+            let mut spec = spec::RunSpec::load("specs/generate_file.json").unwrap();
+            if let Some(strategy) = cmd.get(2).filter(|s| *s != "hill") {
+                spec.strategy = file_strategy(strategy);
+            }
+            if let Some(migration) = cmd.get(3) {
+                spec.migration = Some(migration.clone());
+            }
+            spec.launch(&args).await.unwrap();
+        }
+        "genetic" => {
+            let population = cmd.get(2).map_or(32, |n| n.parse().unwrap());
+            let migration = cmd.get(3).map(|m| m.parse().unwrap());
+            launch_genetic(population, migration, &args).await;
+        }
         "gen_all" => launch_gen_all_puzzles(),
+        "gen_all_archive" => {
+            let size = cmd.get(2).unwrap().parse().unwrap();
+            let piece = cmd.get(3).unwrap().parse().unwrap();
+            let path = cmd.get(4).unwrap();
+            gen_all_archive(size, piece, path);
+        }
+        "generate_archive" => {
+            let path = cmd.get(2).unwrap();
+            let mut spec = spec::RunSpec::load("specs/generate_file.json").unwrap();
+            spec.run = run_name(path);
+            spec.writer = spec::WriterSpec::Archive { path: path.clone() };
+            spec.launch(&args).await.unwrap();
+        }
+        "archive" => {
+            let path = cmd.get(2).unwrap();
+            query_archive(path, cmd.get(3));
+        }
         "solve_sample" => solve_sample_puzzle(),
         "dump" => {
             let name = cmd.get(2).unwrap();
             dump_puzzle(name).await;
         }
+        "stl" => {
+            let code = cmd.get(2).unwrap();
+            let dir = cmd.get(3).unwrap();
+            let ascii = cmd.get(4).is_some_and(|format| format == "ascii");
+            export_stl(code, dir, ascii);
+        }
+        "gltf" => {
+            let code = cmd.get(2).unwrap();
+            let path = cmd.get(3).unwrap();
+            export_gltf(code, path);
+        }
+        "sheet" => {
+            let code = cmd.get(2).unwrap();
+            let path = cmd.get(3).unwrap();
+            export_sheet(code, path);
+        }
+        "voxelize" => {
+            let path = cmd.get(2).unwrap();
+            let size = cmd.get(3).unwrap().parse().unwrap();
+            let pieces = cmd.get(4).unwrap().parse().unwrap();
+            let shape = load_shape(path, size);
+            let puzzle = Puzzle::base_in(&shape, pieces, None);
+            println!("{}", PuzzleNumFormat::from_puzzle(&puzzle).to_block_code());
+        }
+        "generate_shape" => {
+            let path = cmd.get(2).unwrap();
+            let size = cmd.get(3).unwrap().parse().unwrap();
+            let pieces = cmd.get(4).unwrap().parse().unwrap();
+            launch_generate_shape(path, size, pieces, &args).await;
+        }
+        "vox_import" => {
+            let path = cmd.get(2).unwrap();
+            import_vox(path);
+        }
+        "vox_export" => {
+            let code = cmd.get(2).unwrap();
+            let path = cmd.get(3).unwrap();
+            export_vox(code, path);
+        }
+        "features" => {
+            let source = cmd.get(2).unwrap();
+            let path = cmd.get(3).unwrap();
+            export_features(source, path).await;
+        }
+        "list" => {
+            let dir = cmd.get(2).unwrap();
+            list_sidecars(dir);
+        }
         _ => launch_server().await,
     }
 }
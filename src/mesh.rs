@@ -0,0 +1,607 @@
+use std::collections::HashMap;
+
+use crate::cells::{Cells, D6};
+use crate::iters::V3Iter;
+use crate::puzzle::{Piece, Puzzle};
+use crate::v3::{V3, V3F, V3I};
+
+const EPS: f64 = 1e-9;
+/// Largest bevel in units: chamfers of half a unit would meet on a single cube
+const MAX_BEVEL: f64 = 0.45;
+
+#[derive(Clone, Debug)]
+pub struct MeshOptions {
+    /// Length of a unit cube in mm ($burr_scale)
+    pub scale: f64,
+    /// Clearance removed from every outer face in mm ($burr_inset)
+    pub inset: f64,
+    /// Width of the chamfer on convex edges in mm, 0 for sharp edges ($unit_beveled);
+    /// wider ones are narrowed to `MAX_BEVEL` units
+    pub bevel: f64,
+    /// Lay the pieces side by side on the print plate ($auto_layout)
+    pub auto_layout: bool,
+    /// Space between pieces on the print plate in mm
+    pub gap: f64,
+}
+impl Default for MeshOptions {
+    fn default() -> Self {
+        Self {
+            scale: 10.0,
+            inset: 0.07,
+            bevel: 0.5,
+            auto_layout: true,
+            gap: 5.0,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Mesh {
+    /// Counter-clockwise seen from outside
+    pub triangles: Vec<[V3F; 3]>,
+}
+
+impl Mesh {
+    /// Closed mesh of the piece in mm, placed at its assembled position
+    pub fn from_piece(piece: &Piece, options: &MeshOptions) -> Mesh {
+        let bevel = (options.bevel / options.scale).clamp(0.0, MAX_BEVEL);
+        let inset = options.inset / options.scale;
+        let polygons = voxel_polygons(&piece.block, bevel);
+        let polygons = merge_coplanar(polygons);
+        let polygons = split_edges(polygons);
+        let mut triangles = polygons.iter().flat_map(triangulate).collect::<Vec<_>>();
+        if inset > 0.0 {
+            inset_vertices(&mut triangles, inset);
+        }
+        let mut mesh = Mesh { triangles };
+        mesh.scale(options.scale);
+        mesh
+    }
+    /// All pieces on one print plate, resting on z=0
+    pub fn plate(puzzle: &Puzzle, options: &MeshOptions) -> Mesh {
+        let meshes = puzzle
+            .pieces
+            .iter()
+            .map(|piece| Mesh::from_piece(piece, options))
+            .collect::<Vec<_>>();
+        let mut plate = Mesh::default();
+        if !options.auto_layout {
+            for mesh in meshes.iter() {
+                plate.append(mesh);
+            }
+            let (min, _) = plate.bounding_box();
+            plate.translate(V3F(0.0, 0.0, -min.2));
+            return plate;
+        }
+        let columns = (meshes.len() as f64).sqrt().ceil() as usize;
+        let (mut x, mut y, mut depth) = (0.0, 0.0, 0.0f64);
+        for (i, mesh) in meshes.into_iter().enumerate() {
+            if i > 0 && i % columns == 0 {
+                x = 0.0;
+                y += depth + options.gap;
+                depth = 0.0;
+            }
+            let mut mesh = mesh;
+            let (min, max) = mesh.bounding_box();
+            mesh.translate(V3F(x, y, 0.0) - min);
+            x += max.0 - min.0 + options.gap;
+            depth = depth.max(max.1 - min.1);
+            plate.append(&mesh);
+        }
+        plate
+    }
+    pub fn append(&mut self, other: &Mesh) {
+        self.triangles.extend(other.triangles.iter().cloned());
+    }
+    pub fn translate(&mut self, v: V3F) {
+        for triangle in self.triangles.iter_mut() {
+            for p in triangle.iter_mut() {
+                *p = *p + v;
+            }
+        }
+    }
+    pub fn scale(&mut self, s: f64) {
+        for triangle in self.triangles.iter_mut() {
+            for p in triangle.iter_mut() {
+                *p = *p * s;
+            }
+        }
+    }
+    pub fn bounding_box(&self) -> (V3F, V3F) {
+        let inf = f64::INFINITY;
+        let mut min = V3F(inf, inf, inf);
+        let mut max = V3F(-inf, -inf, -inf);
+        for p in self.triangles.iter().flatten() {
+            min = min.min(p);
+            max = max.max(p);
+        }
+        (min, max)
+    }
+    /// Every edge is used by the same number of triangles in both directions
+    pub fn is_watertight(&self) -> bool {
+        let mut edges: HashMap<(Key, Key), isize> = HashMap::new();
+        for triangle in self.triangles.iter() {
+            for i in 0..3 {
+                let a = key(&triangle[i]);
+                let b = key(&triangle[(i + 1) % 3]);
+                if a < b {
+                    *edges.entry((a, b)).or_default() += 1;
+                } else {
+                    *edges.entry((b, a)).or_default() -= 1;
+                }
+            }
+        }
+        edges.values().all(|&count| count == 0)
+    }
+}
+
+type Key = (i64, i64, i64);
+fn key(p: &V3F) -> Key {
+    let q = |x: f64| (x * 1e6).round() as i64;
+    (q(p.0), q(p.1), q(p.2))
+}
+
+/// Planar convex polygon, counter-clockwise around `normal`
+#[derive(Clone, Debug)]
+struct Polygon {
+    points: Vec<V3F>,
+    normal: V3F,
+}
+impl Polygon {
+    fn plane_key(&self) -> (Key, i64) {
+        let offset = self.normal.dot(&self.points[0]);
+        (key(&self.normal), (offset * 1e6).round() as i64)
+    }
+    fn area(&self) -> f64 {
+        let mut sum = V3F::default();
+        for i in 0..self.points.len() {
+            sum = sum + self.points[i].cross(&self.points[(i + 1) % self.points.len()]);
+        }
+        sum.dot(&self.normal) / 2.0
+    }
+}
+
+/// `normal . x <= offset`
+#[derive(Clone, Debug)]
+struct HalfSpace {
+    normal: V3F,
+    offset: f64,
+}
+impl HalfSpace {
+    fn distance(&self, p: &V3F) -> f64 {
+        self.normal.dot(p) - self.offset
+    }
+    fn flip(&self) -> HalfSpace {
+        HalfSpace {
+            normal: self.normal * -1.0,
+            offset: -self.offset,
+        }
+    }
+}
+
+fn clip(polygon: &Polygon, half: &HalfSpace) -> Option<Polygon> {
+    let mut points: Vec<V3F> = vec![];
+    let n = polygon.points.len();
+    for i in 0..n {
+        let a = polygon.points[i];
+        let b = polygon.points[(i + 1) % n];
+        let (da, db) = (half.distance(&a), half.distance(&b));
+        if da <= EPS {
+            points.push(a);
+        }
+        if (da < -EPS && db > EPS) || (da > EPS && db < -EPS) {
+            points.push(a + (b - a) * (da / (da - db)));
+        }
+    }
+    points.dedup_by(|a, b| key(a) == key(b));
+    while points.len() > 1 && key(&points[0]) == key(points.last().unwrap()) {
+        points.pop();
+    }
+    let polygon = Polygon {
+        points,
+        normal: polygon.normal,
+    };
+    if polygon.points.len() < 3 || polygon.area() < EPS {
+        return None;
+    }
+    Some(polygon)
+}
+
+fn filled(block: &Cells, p: V3I) -> bool {
+    p.into_v3_in(&V3::cube(block.size))
+        .is_some_and(|p| block.getv(p))
+}
+
+/// Pairs of perpendicular directions, one per cube edge
+fn cube_edges() -> Vec<(V3I, V3I)> {
+    D6.iter()
+        .enumerate()
+        .flat_map(|(i, &d1)| {
+            D6.iter()
+                .enumerate()
+                .skip(i + 1)
+                .filter(move |&(j, _)| i / 2 != j / 2)
+                .map(move |(_, &d2)| (d1, d2))
+        })
+        .collect()
+}
+
+/// Chamfers of the convex edges of the voxel
+fn chamfers(block: &Cells, p: V3I, bevel: f64) -> Vec<HalfSpace> {
+    if bevel <= 0.0 {
+        return vec![];
+    }
+    let center = V3F::from(p) + V3F(0.5, 0.5, 0.5);
+    cube_edges()
+        .into_iter()
+        .filter(|&(d1, d2)| {
+            !filled(block, p + d1) && !filled(block, p + d2) && !filled(block, p + d1 + d2)
+        })
+        .map(|(d1, d2)| {
+            let normal = V3F::from(d1 + d2);
+            HalfSpace {
+                normal,
+                offset: normal.dot(&center) + 1.0 - bevel,
+            }
+        })
+        .collect()
+}
+
+fn face_square(p: V3I, d: V3I) -> Polygon {
+    let n = V3F::from(d);
+    let u = V3F(n.2.abs(), n.0.abs(), n.1.abs());
+    let v = n.cross(&u);
+    let face = V3F::from(p) + V3F(0.5, 0.5, 0.5) + n * 0.5;
+    let points = [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)]
+        .iter()
+        .map(|&(a, b)| face + u * (a * 0.5) + v * (b * 0.5))
+        .collect();
+    Polygon { points, normal: n }
+}
+
+fn chamfer_rect(p: V3I, d1: V3I, d2: V3I, bevel: f64) -> Polygon {
+    let center = V3F::from(p) + V3F(0.5, 0.5, 0.5);
+    let (f1, f2) = (V3F::from(d1), V3F::from(d2));
+    let e = f1.cross(&f2) * 0.5;
+    let p1 = center + f1 * (0.5 - bevel) + f2 * 0.5;
+    let p2 = center + f1 * 0.5 + f2 * (0.5 - bevel);
+    Polygon {
+        points: vec![p1 - e, p1 + e, p2 + e, p2 - e],
+        normal: (f1 + f2).normalize(),
+    }
+}
+
+fn clip_all(polygon: Polygon, halves: &[HalfSpace]) -> Option<Polygon> {
+    halves
+        .iter()
+        .try_fold(polygon, |polygon, half| clip(&polygon, half))
+}
+
+/// Boundary of the union of the chamfered unit cubes, in unit coordinates
+fn voxel_polygons(block: &Cells, bevel: f64) -> Vec<Polygon> {
+    let mut polygons = vec![];
+    for v in V3Iter::cube(block.size) {
+        if !block.getv(v) {
+            continue;
+        }
+        let p = V3I::from(v);
+        let cuts = chamfers(block, p, bevel);
+        for d in D6 {
+            let face = face_square(p, d);
+            let Some(own) = clip_all(face, &cuts) else {
+                continue;
+            };
+            if !filled(block, p + d) {
+                polygons.push(own);
+                continue;
+            }
+            // Faces between two voxels are inside, except where only the neighbor is chamfered
+            let other_cuts = chamfers(block, p + d, bevel);
+            for cut in other_cuts.iter() {
+                let shared = cuts.iter().any(|c| {
+                    key(&c.normal) == key(&cut.normal) && (c.offset - cut.offset).abs() < EPS
+                });
+                if shared {
+                    continue;
+                }
+                if let Some(exposed) = clip(&own, &cut.flip()) {
+                    polygons.push(exposed);
+                }
+            }
+        }
+        for (d1, d2) in cube_edges() {
+            let normal = V3F::from(d1 + d2);
+            let Some(k) = cuts.iter().position(|c| key(&c.normal) == key(&normal)) else {
+                continue;
+            };
+            let others = cuts
+                .iter()
+                .enumerate()
+                .filter(|&(j, _)| j != k)
+                .map(|(_, c)| c.clone())
+                .collect::<Vec<_>>();
+            if let Some(rect) = clip_all(chamfer_rect(p, d1, d2, bevel), &others) {
+                polygons.push(rect);
+            }
+        }
+    }
+    polygons
+}
+
+fn is_convex(points: &[V3F], normal: &V3F) -> bool {
+    let n = points.len();
+    (0..n).all(|i| {
+        let a = points[i];
+        let b = points[(i + 1) % n];
+        let c = points[(i + 2) % n];
+        (b - a).cross(&(c - b)).dot(normal) >= -EPS
+    })
+}
+
+fn remove_collinear(points: Vec<V3F>, normal: &V3F) -> Vec<V3F> {
+    let n = points.len();
+    (0..n)
+        .filter(|&i| {
+            let a = points[(i + n - 1) % n];
+            let b = points[i];
+            let c = points[(i + 1) % n];
+            (b - a).cross(&(c - b)).dot(normal) > EPS
+        })
+        .map(|i| points[i])
+        .collect()
+}
+
+/// Join coplanar polygons sharing an edge while they stay convex
+fn merge_coplanar(polygons: Vec<Polygon>) -> Vec<Polygon> {
+    let mut planes: HashMap<(Key, i64), Vec<Polygon>> = HashMap::new();
+    for polygon in polygons {
+        planes.entry(polygon.plane_key()).or_default().push(polygon);
+    }
+    let mut keys = planes.keys().cloned().collect::<Vec<_>>();
+    keys.sort();
+    let mut result = vec![];
+    for plane in keys {
+        let mut group = planes.remove(&plane).unwrap();
+        'merge: loop {
+            for i in 0..group.len() {
+                for j in i + 1..group.len() {
+                    if let Some(merged) = merge_pair(&group[i], &group[j]) {
+                        group.swap_remove(j);
+                        group[i] = merged;
+                        continue 'merge;
+                    }
+                }
+            }
+            break;
+        }
+        result.extend(group);
+    }
+    result
+}
+
+fn merge_pair(a: &Polygon, b: &Polygon) -> Option<Polygon> {
+    let (na, nb) = (a.points.len(), b.points.len());
+    for i in 0..na {
+        let (p, q) = (key(&a.points[i]), key(&a.points[(i + 1) % na]));
+        for j in 0..nb {
+            if key(&b.points[j]) != q || key(&b.points[(j + 1) % nb]) != p {
+                continue;
+            }
+            // a from q around to p, then b from after p to before q
+            let mut points = vec![];
+            for k in 0..na {
+                points.push(a.points[(i + 1 + k) % na]);
+            }
+            for k in 2..nb {
+                points.push(b.points[(j + k) % nb]);
+            }
+            if !is_convex(&points, &a.normal) {
+                return None;
+            }
+            return Some(Polygon {
+                points: remove_collinear(points, &a.normal),
+                normal: a.normal,
+            });
+        }
+    }
+    None
+}
+
+/// Insert vertices lying on the edges of other polygons to avoid T-junctions
+fn split_edges(polygons: Vec<Polygon>) -> Vec<Polygon> {
+    let mut vertices: HashMap<Key, V3F> = HashMap::new();
+    for p in polygons.iter().flat_map(|polygon| polygon.points.iter()) {
+        vertices.insert(key(p), *p);
+    }
+    let vertices = vertices.into_values().collect::<Vec<_>>();
+    polygons
+        .into_iter()
+        .map(|polygon| {
+            let n = polygon.points.len();
+            let mut points = vec![];
+            for i in 0..n {
+                let a = polygon.points[i];
+                let b = polygon.points[(i + 1) % n];
+                let (min, max) = (a.min(&b), a.max(&b));
+                let ab = b - a;
+                let length = ab.dot(&ab);
+                let mut inner = vertices
+                    .iter()
+                    .filter(|v| {
+                        v.0 >= min.0 - EPS
+                            && v.1 >= min.1 - EPS
+                            && v.2 >= min.2 - EPS
+                            && v.0 <= max.0 + EPS
+                            && v.1 <= max.1 + EPS
+                            && v.2 <= max.2 + EPS
+                    })
+                    .filter(|&v| ab.cross(&(*v - a)).norm() < 1e-7)
+                    .map(|v| ((*v - a).dot(&ab) / length, *v))
+                    .filter(|&(t, v)| {
+                        t > 0.0 && t < 1.0 && key(&v) != key(&a) && key(&v) != key(&b)
+                    })
+                    .collect::<Vec<_>>();
+                inner.sort_by(|x, y| x.0.total_cmp(&y.0));
+                points.push(a);
+                points.extend(inner.into_iter().map(|(_, v)| v));
+            }
+            Polygon {
+                points,
+                normal: polygon.normal,
+            }
+        })
+        .collect()
+}
+
+fn triangulate(polygon: &Polygon) -> Vec<[V3F; 3]> {
+    let points = &polygon.points;
+    let n = points.len();
+    let area = |a: &V3F, b: &V3F, c: &V3F| (*b - *a).cross(&(*c - *a)).dot(&polygon.normal);
+    for apex in 0..n {
+        let fan = (1..n - 1)
+            .map(|k| {
+                [
+                    points[apex],
+                    points[(apex + k) % n],
+                    points[(apex + k + 1) % n],
+                ]
+            })
+            .collect::<Vec<_>>();
+        if fan.iter().all(|[a, b, c]| area(a, b, c) > EPS) {
+            return fan;
+        }
+    }
+    // Every corner has collinear neighbors, fan from the centroid instead
+    let center = points.iter().fold(V3F::default(), |s, p| s + *p) * (1.0 / n as f64);
+    (0..n)
+        .map(|k| [center, points[k], points[(k + 1) % n]])
+        .collect()
+}
+
+/// Move every vertex so that each of its planes moves inward by `inset`
+fn inset_vertices(triangles: &mut [[V3F; 3]], inset: f64) {
+    let mut normals: HashMap<Key, Vec<V3F>> = HashMap::new();
+    for [a, b, c] in triangles.iter() {
+        let normal = (*b - *a).cross(&(*c - *a)).normalize();
+        for p in [a, b, c] {
+            let list = normals.entry(key(p)).or_default();
+            if !list.iter().any(|n| key(n) == key(&normal)) {
+                list.push(normal);
+            }
+        }
+    }
+    let shifts = normals
+        .into_iter()
+        .map(|(k, list)| (k, plane_shift(&list, inset)))
+        .collect::<HashMap<_, _>>();
+    for p in triangles.iter_mut().flatten() {
+        *p = *p + shifts[&key(p)];
+    }
+}
+
+/// Smallest d with n . d = -inset for every normal, in the least squares sense
+fn plane_shift(normals: &[V3F], inset: f64) -> V3F {
+    let mut basis: Vec<V3F> = vec![];
+    for n in normals {
+        let rest = basis.iter().fold(*n, |r, b| r - *b * r.dot(b));
+        if rest.norm() > 1e-6 {
+            basis.push(rest.normalize());
+        }
+    }
+    let k = basis.len();
+    let mut matrix = vec![vec![0.0; k + 1]; k];
+    for n in normals {
+        let coord = basis.iter().map(|b| n.dot(b)).collect::<Vec<_>>();
+        for i in 0..k {
+            for j in 0..k {
+                matrix[i][j] += coord[i] * coord[j];
+            }
+            matrix[i][k] -= coord[i] * inset;
+        }
+    }
+    for i in 0..k {
+        let pivot = (i..k)
+            .max_by(|&a, &b| matrix[a][i].abs().total_cmp(&matrix[b][i].abs()))
+            .unwrap();
+        matrix.swap(i, pivot);
+        for r in 0..k {
+            if r != i {
+                let f = matrix[r][i] / matrix[i][i];
+                let row = matrix[i].clone();
+                for (x, y) in matrix[r].iter_mut().zip(row).skip(i) {
+                    *x -= f * y;
+                }
+            }
+        }
+    }
+    (0..k).fold(V3F::default(), |d, i| {
+        d + basis[i] * (matrix[i][k] / matrix[i][i])
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::sample_puzzle;
+
+    fn volume(mesh: &Mesh) -> f64 {
+        mesh.triangles
+            .iter()
+            .map(|[a, b, c]| a.dot(&b.cross(c)) / 6.0)
+            .sum()
+    }
+
+    fn sharp() -> MeshOptions {
+        MeshOptions {
+            scale: 1.0,
+            inset: 0.0,
+            bevel: 0.0,
+            ..MeshOptions::default()
+        }
+    }
+    #[test]
+    fn test_cube_mesh() {
+        let piece = Piece::from_str(2, "x.|..\n..|..");
+        let mesh = Mesh::from_piece(&piece, &sharp());
+        assert_eq!(mesh.triangles.len(), 12);
+        assert!(mesh.is_watertight());
+        assert!((volume(&mesh) - 1.0).abs() < 1e-9);
+    }
+    #[test]
+    fn test_merge_coplanar() {
+        let piece = Piece::from_str(3, "xxx|...|...\n...|...|...\n...|...|...");
+        let mesh = Mesh::from_piece(&piece, &sharp());
+        assert_eq!(mesh.triangles.len(), 12);
+        assert!((volume(&mesh) - 3.0).abs() < 1e-9);
+    }
+    #[test]
+    fn test_bevel_inset_watertight() {
+        let puzzle = sample_puzzle();
+        let options = MeshOptions::default();
+        for piece in puzzle.pieces.iter() {
+            let mesh = Mesh::from_piece(piece, &options);
+            assert!(mesh.is_watertight());
+            let full = piece.block.count() as f64 * options.scale.powi(3);
+            assert!(volume(&mesh) < full);
+            assert!(volume(&mesh) > full * 0.9);
+        }
+    }
+    #[test]
+    fn wide_bevel_is_narrowed() {
+        let piece = Piece::from_str(2, "xx|x.\n..|..");
+        let options = MeshOptions {
+            bevel: 1.0,
+            ..sharp()
+        };
+        let mesh = Mesh::from_piece(&piece, &options);
+        assert!(mesh.is_watertight());
+        assert!(volume(&mesh) > 0.0);
+    }
+    #[test]
+    fn test_plate_on_floor() {
+        let mesh = Mesh::plate(&sample_puzzle(), &MeshOptions::default());
+        let (min, _) = mesh.bounding_box();
+        assert!(min.2.abs() < 1e-9);
+        assert!(mesh.is_watertight());
+    }
+}
@@ -0,0 +1,210 @@
+use itertools::Itertools;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::cells::{Cells, D6};
+use crate::grow::partition;
+use crate::iters::V3Iter;
+use crate::puzzle::{merge_stray_parts, Piece, Puzzle};
+use crate::searcher::{PuzzleConstraints, PuzzleGenerator};
+use crate::v3::{V3, V3I};
+
+/// Tries before giving the puzzle back unchanged, when no mutation applies
+const ATTEMPTS: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Mutation {
+    /// A piece into two connected parts
+    Split,
+    /// Two touching pieces into one
+    Merge,
+    /// A cell of a piece becomes a hole, and a hole joins a piece next to it
+    MoveHole,
+    /// Two touching pieces trade a patch of cells at their boundary
+    Exchange,
+}
+
+/// Changes the piece and hole structure, with one mutation picked at random per call.
+/// Parts cut off by a mutation are repaired by `merge_stray_parts`.
+#[derive(Clone, Debug)]
+pub struct StructurePuzzleGenerator<C: PuzzleConstraints> {
+    pub mutations: Vec<Mutation>,
+    /// Piece count kept by split and merge
+    pub min_pieces: usize,
+    pub max_pieces: usize,
+    pub constraints: C,
+}
+impl<C: PuzzleConstraints> StructurePuzzleGenerator<C> {
+    fn mutate(
+        &self,
+        mutation: Mutation,
+        blocks: &mut Vec<Cells>,
+        rnd: &mut impl Rng,
+    ) -> Option<()> {
+        match mutation {
+            Mutation::Split => {
+                if blocks.len() >= self.max_pieces {
+                    return None;
+                }
+                let splittable = (0..blocks.len())
+                    .filter(|&i| blocks[i].count() >= 2)
+                    .collect_vec();
+                let i = pick(&splittable, rnd)?;
+                let parts = partition(&blocks[i], &[], 2, rnd)?;
+                blocks[i] = parts[0].clone();
+                blocks.push(parts[1].clone());
+            }
+            Mutation::Merge => {
+                if blocks.len() <= self.min_pieces.max(1) {
+                    return None;
+                }
+                let (a, b) = pick(&touching_pairs(blocks), rnd)?;
+                let merged = blocks.remove(b);
+                blocks[a].or_inplace(&merged);
+            }
+            Mutation::MoveHole => {
+                let size = blocks.first()?.size;
+                let owner = |blocks: &[Cells], x: V3| blocks.iter().position(|b| b.getv(x));
+                let holes = V3Iter::cube(size)
+                    .filter(|&x| owner(blocks, x).is_none())
+                    .collect_vec();
+                let hole = pick(&holes, rnd)?;
+                let filled = V3Iter::cube(size)
+                    .filter(|&x| owner(blocks, x).is_some())
+                    .collect_vec();
+                let cell = pick(&filled, rnd)?;
+                let from = owner(blocks, cell)?;
+                blocks[from].setv(cell, false);
+                let next = neighbours(hole, size)
+                    .filter_map(|n| owner(blocks, n))
+                    .collect_vec();
+                let to = pick(&next, rnd)?;
+                blocks[to].setv(hole, true);
+            }
+            Mutation::Exchange => {
+                let (a, b) = pick(&touching_pairs(blocks), rnd)?;
+                let patch_a = patch(&blocks[a], &blocks[b], rnd)?;
+                let patch_b = patch(&blocks[b], &blocks[a], rnd)?;
+                blocks[a].and_not_inplace(&patch_a);
+                blocks[a].or_inplace(&patch_b);
+                blocks[b].and_not_inplace(&patch_b);
+                blocks[b].or_inplace(&patch_a);
+            }
+        }
+        Some(())
+    }
+}
+impl<C: PuzzleConstraints> PuzzleGenerator for StructurePuzzleGenerator<C> {
+    fn generate(&self, puzzle: &Puzzle, rnd: &mut impl Rng) -> Puzzle {
+        let init_blocks = puzzle
+            .pieces
+            .iter()
+            .map(|piece| piece.block.clone())
+            .collect_vec();
+        for _ in 0..ATTEMPTS {
+            let Some(mutation) = pick(&self.mutations, rnd) else {
+                break;
+            };
+            let mut blocks = init_blocks.clone();
+            if self.mutate(mutation, &mut blocks, rnd).is_none() {
+                continue;
+            }
+            merge_stray_parts(&mut blocks);
+            if !blocks.iter().all(|b| b.count() > 0 && b.is_connected()) {
+                continue;
+            }
+            if !self.constraints.is_ok(&blocks) {
+                continue;
+            }
+            let mut puzzle = puzzle.clone();
+            puzzle.pieces = blocks.iter().map(Piece::from_block).collect();
+            return puzzle;
+        }
+        println!("WARN: no applicable mutation of {:?}", self.mutations);
+        puzzle.clone()
+    }
+}
+
+fn pick<T: Copy>(items: &[T], rnd: &mut impl Rng) -> Option<T> {
+    if items.is_empty() {
+        return None;
+    }
+    Some(items[rnd.gen_range(0..items.len())])
+}
+fn neighbours(x: V3, size: usize) -> impl Iterator<Item = V3> {
+    D6.into_iter()
+        .filter_map(move |d| (V3I::from(x) + d).into_v3_in(&V3::cube(size)))
+}
+fn touching_pairs(blocks: &[Cells]) -> Vec<(usize, usize)> {
+    (0..blocks.len())
+        .tuple_combinations()
+        .filter(|&(a, b)| blocks[a].touches(&blocks[b]))
+        .collect()
+}
+/// Connected cells of `block` grown from a cell next to `other`, up to half of `block`
+fn patch(block: &Cells, other: &Cells, rnd: &mut impl Rng) -> Option<Cells> {
+    let size = block.size;
+    let boundary = V3Iter::cube(size)
+        .filter(|&x| block.getv(x) && neighbours(x, size).any(|n| other.getv(n)))
+        .collect_vec();
+    let start = pick(&boundary, rnd)?;
+    let limit = rnd.gen_range(1..=(block.count() / 2).max(1));
+    let mut patch = Cells::empty(size);
+    let mut frontier = vec![start];
+    let mut count = 0;
+    while count < limit && !frontier.is_empty() {
+        let x = frontier.swap_remove(rnd.gen_range(0..frontier.len()));
+        if patch.getv(x) {
+            continue;
+        }
+        patch.setv(x, true);
+        count += 1;
+        frontier.extend(neighbours(x, size).filter(|&n| block.getv(n) && !patch.getv(n)));
+    }
+    Some(patch)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::searcher::{SearchRng, TerminalPuzzleConstraints};
+    use rand::SeedableRng;
+    fn mutated(mutation: Mutation, puzzle: &Puzzle, rnd: &mut SearchRng) -> Puzzle {
+        let generator = StructurePuzzleGenerator {
+            mutations: vec![mutation],
+            min_pieces: 2,
+            max_pieces: 4,
+            constraints: TerminalPuzzleConstraints {},
+        };
+        let puzzle = generator.generate(puzzle, rnd);
+        let mut all = Cells::empty(puzzle.size);
+        for piece in puzzle.pieces.iter() {
+            assert!(piece.block.count() > 0 && piece.block.is_connected());
+            assert!(!all.overlap(&piece.block));
+            all.or_inplace(&piece.block);
+        }
+        puzzle
+    }
+    fn cells(puzzle: &Puzzle) -> usize {
+        puzzle.pieces.iter().map(|p| p.block.count()).sum()
+    }
+    #[test]
+    fn test_mutations() {
+        let mut rnd = SearchRng::seed_from_u64(0);
+        let base = Puzzle::base(3, 3, 2, None);
+        let split = mutated(Mutation::Split, &base, &mut rnd);
+        assert_eq!((split.pieces.len(), cells(&split)), (4, 25));
+        // at `max_pieces`
+        let again = mutated(Mutation::Split, &split, &mut rnd);
+        assert_eq!(again.pieces.len(), 4);
+        let merged = mutated(Mutation::Merge, &base, &mut rnd);
+        assert_eq!((merged.pieces.len(), cells(&merged)), (2, 25));
+        for _ in 0..10 {
+            let moved = mutated(Mutation::MoveHole, &base, &mut rnd);
+            assert_eq!((moved.pieces.len(), cells(&moved)), (3, 25));
+            let exchanged = mutated(Mutation::Exchange, &base, &mut rnd);
+            assert_eq!((exchanged.pieces.len(), cells(&exchanged)), (3, 25));
+        }
+    }
+}
@@ -0,0 +1,148 @@
+use std::cmp::{Ordering, Reverse};
+
+use serde::{Deserialize, Serialize};
+
+use crate::printability::Printability;
+use crate::puzzle::{Puzzle, SolveResult};
+use crate::searcher::{first_remove, lexicographic, EvalValue, Evaluator};
+
+/// Items whose values no other item weakly dominates
+#[derive(Debug, Clone)]
+pub struct ParetoFront<T, V> {
+    pub entries: Vec<(T, V)>,
+}
+impl<T, V> Default for ParetoFront<T, V> {
+    fn default() -> Self {
+        Self { entries: vec![] }
+    }
+}
+impl<T, V: EvalValue> ParetoFront<T, V> {
+    /// Drops the entries `value` dominates; `false` and unchanged if an entry dominates it
+    pub fn insert(&mut self, item: T, value: V) -> bool {
+        if self.entries.iter().any(|(_, v)| v.dominates(&value)) {
+            return false;
+        }
+        self.entries.retain(|(_, v)| !value.dominates(v));
+        self.entries.push((item, value));
+        true
+    }
+}
+
+/// Objectives of `ParetoEvaluator`. `Ord` is lexicographic in the field order, for the
+/// strategies; the front uses `dominates`.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct ParetoValue {
+    /// Moves before the first removal, more is better
+    pub level: usize,
+    /// Fewer is better
+    pub pieces: usize,
+    /// Solver states without a new next state, more is better
+    pub dead_ends: usize,
+    /// Overhanging cells and thin necks of all pieces, fewer is better
+    pub unprintable: usize,
+}
+impl ParetoValue {
+    fn key(&self) -> (usize, Reverse<usize>, usize, Reverse<usize>) {
+        (
+            self.level,
+            Reverse(self.pieces),
+            self.dead_ends,
+            Reverse(self.unprintable),
+        )
+    }
+}
+impl Ord for ParetoValue {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+impl PartialOrd for ParetoValue {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl EvalValue for ParetoValue {
+    const PARETO: bool = true;
+    fn to_str(&self) -> String {
+        format!(
+            "level={} pieces={} dead_ends={} unprintable={}",
+            self.level, self.pieces, self.dead_ends, self.unprintable
+        )
+    }
+    fn to_path(&self) -> String {
+        format!(
+            "L{}P{}D{}U{}",
+            self.level, self.pieces, self.dead_ends, self.unprintable
+        )
+    }
+    fn to_scalar(&self) -> f64 {
+        lexicographic(&[
+            self.level,
+            999 - self.pieces.min(999),
+            self.dead_ends,
+            999 - self.unprintable.min(999),
+        ])
+    }
+    fn dominates(&self, other: &Self) -> bool {
+        self.level >= other.level
+            && self.pieces <= other.pieces
+            && self.dead_ends >= other.dead_ends
+            && self.unprintable <= other.unprintable
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ParetoEvaluator {}
+impl Evaluator for ParetoEvaluator {
+    type Value = ParetoValue;
+    fn evaluate(&self, puzzle: &Puzzle, result: &SolveResult) -> Self::Value {
+        let unprintable = puzzle
+            .pieces
+            .iter()
+            .map(|piece| {
+                let printability = Printability::of(&piece.block);
+                printability.overhangs + printability.necks.len()
+            })
+            .sum();
+        ParetoValue {
+            level: first_remove(&result.shrink_move(&result.moves(puzzle))),
+            pieces: puzzle.pieces.len(),
+            dead_ends: result.dead_ends(),
+            unprintable,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::sample_puzzle;
+    fn value(level: usize, pieces: usize, dead_ends: usize, unprintable: usize) -> ParetoValue {
+        ParetoValue {
+            level,
+            pieces,
+            dead_ends,
+            unprintable,
+        }
+    }
+    #[test]
+    fn test_pareto_front() {
+        let mut front = ParetoFront::default();
+        assert!(front.insert("a", value(3, 5, 10, 2)));
+        // a trade-off
+        assert!(front.insert("b", value(2, 4, 10, 2)));
+        assert!(!front.insert("c", value(2, 5, 10, 2)));
+        assert!(!front.insert("a again", value(3, 5, 10, 2)));
+        // dominates both
+        assert!(front.insert("d", value(3, 4, 11, 0)));
+        assert_eq!(front.entries.len(), 1);
+        assert_eq!(front.entries[0].0, "d");
+        assert!(value(3, 5, 0, 0) > value(2, 1, 99, 0));
+        assert!(value(2, 4, 0, 0).to_scalar() > value(2, 5, 0, 0).to_scalar());
+
+        let puzzle = sample_puzzle();
+        let value = ParetoEvaluator {}.evaluate(&puzzle, &puzzle.solve());
+        assert_eq!(value.pieces, puzzle.pieces.len());
+        assert!(value.level > 0 && value.dead_ends > 0);
+    }
+}
@@ -0,0 +1,115 @@
+use std::cmp::Reverse;
+
+use itertools::Itertools;
+
+use crate::cells::{Cells, D6};
+use crate::iters::V3Iter;
+use crate::puzzle::Piece;
+use crate::v3::{V3, V3I};
+
+/// How a piece prints on its own, in its best orientation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Printability {
+    /// Direction of gravity, the resting face is the one facing it
+    pub down: V3I,
+    /// Cells on the build plate
+    pub contact: usize,
+    /// Cells with no cell below them, directly or diagonally (45 degrees)
+    pub overhangs: usize,
+    /// Thin necks, cells whose removal disconnects the piece
+    pub necks: Vec<V3>,
+}
+impl Printability {
+    /// Rests the piece on the face with the fewest overhangs, then the largest contact
+    pub fn of(block: &Cells) -> Self {
+        let cells = V3Iter::cube(block.size)
+            .filter(|&x| block.getv(x))
+            .collect_vec();
+        let (down, (contact, overhangs)) = D6
+            .iter()
+            .map(|&down| (down, support(block, &cells, down)))
+            .min_by_key(|&(_, (contact, overhangs))| (overhangs, Reverse(contact)))
+            .unwrap();
+        Self {
+            down,
+            contact,
+            overhangs,
+            necks: necks(block, &cells),
+        }
+    }
+}
+
+impl Piece {
+    pub fn printability(&self) -> Printability {
+        Printability::of(&self.block)
+    }
+}
+
+fn dot(x: V3I, d: V3I) -> isize {
+    x.0 * d.0 + x.1 * d.1 + x.2 * d.2
+}
+/// Cells on the build plate and overhanging cells, when resting against `down`
+fn support(block: &Cells, cells: &[V3], down: V3I) -> (usize, usize) {
+    let Some(bottom) = cells.iter().map(|&x| dot(V3I::from(x), down)).max() else {
+        return (0, 0);
+    };
+    let sides = D6.iter().filter(|&&d| dot(d, down) == 0).collect_vec();
+    let filled = |x: V3I| {
+        x.into_v3_in(&V3::cube(block.size))
+            .is_some_and(|x| block.getv(x))
+    };
+    let mut contact = 0;
+    let mut overhangs = 0;
+    for &x in cells {
+        let x = V3I::from(x);
+        if dot(x, down) == bottom {
+            contact += 1;
+        } else if !filled(x + down) && !sides.iter().any(|&&d| filled(x + down + d)) {
+            overhangs += 1;
+        }
+    }
+    (contact, overhangs)
+}
+fn necks(block: &Cells, cells: &[V3]) -> Vec<V3> {
+    if cells.len() < 3 {
+        return vec![];
+    }
+    cells
+        .iter()
+        .copied()
+        .filter(|&x| {
+            let mut rest = block.clone();
+            rest.setv(x, false);
+            !rest.is_connected()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_printability() {
+        // T on its stem, with one arm too long for 45 degrees
+        let mut block = Cells::empty(4);
+        for x in [V3(1, 0, 0), V3(1, 0, 1)] {
+            block.setv(x, true);
+        }
+        for x in 0..4 {
+            block.set(x, 0, 2, true);
+        }
+        let cells = V3Iter::cube(4).filter(|&x| block.getv(x)).collect_vec();
+        assert_eq!(support(&block, &cells, V3I(0, 0, -1)), (1, 1));
+        assert_eq!(support(&block, &cells, V3I(0, 0, 1)), (4, 0));
+
+        let printability = Piece::from_block(&block).printability();
+        // lying flat
+        assert_eq!(printability.down.1.abs(), 1);
+        assert_eq!(printability.contact, 6);
+        assert_eq!(printability.overhangs, 0);
+        assert_eq!(
+            printability.necks,
+            vec![V3(1, 0, 1), V3(1, 0, 2), V3(2, 0, 2)]
+        );
+    }
+}
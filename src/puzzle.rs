@@ -26,6 +26,8 @@ pub struct SolveResult {
     pub ok: bool,
     step: Option<usize>,
     reached: HashMap<State, State>,
+    /// Expanded states whose next states were all reached already
+    dead_ends: usize,
     end_state: Option<State>,
 }
 
@@ -80,6 +82,27 @@ fn state_to_str(size: usize, state: &State) -> String {
     s
 }
 
+/// Hands parts split off from a block to another block they touch, while possible
+pub fn merge_stray_parts(blocks: &mut [Cells]) {
+    'repair: loop {
+        for i in 0..blocks.len() {
+            let parts = blocks[i].components();
+            if parts.len() <= 1 {
+                continue;
+            }
+            for part in parts.iter().skip(1) {
+                let touching = (0..blocks.len()).find(|&j| j != i && part.touches(&blocks[j]));
+                if let Some(j) = touching {
+                    blocks[i].and_not_inplace(part);
+                    blocks[j].or_inplace(part);
+                    continue 'repair;
+                }
+            }
+        }
+        break;
+    }
+}
+
 impl Puzzle {
     fn init_state(&self) -> State {
         let init_pos = Cells::to_index(self.space, self.margin, self.margin, self.margin);
@@ -98,6 +121,7 @@ impl Puzzle {
                     ok: false,
                     step: None,
                     reached: HashMap::new(),
+                    dead_ends: 0,
                     end_state: None,
                 };
             }
@@ -107,8 +131,10 @@ impl Puzzle {
     pub fn solve_whole(&self, log: bool) -> SolveResult {
         use std::cmp::Reverse;
         let mut reached = HashMap::new();
+        let mut dead_ends = 0;
+        let init_state = self.init_state();
         let mut queue = std::collections::BinaryHeap::new();
-        queue.push(Reverse((self.init_state(), 0)));
+        queue.push(Reverse((init_state.clone(), 0)));
         while let Some(Reverse((state, step))) = queue.pop() {
             if self.is_solved(&state) {
                 if log {
@@ -118,6 +144,7 @@ impl Puzzle {
                     ok: true,
                     step: Some(step),
                     reached,
+                    dead_ends,
                     end_state: Some(state),
                 };
             }
@@ -129,10 +156,14 @@ impl Puzzle {
                     break;
                 }
             }
+            // the initial state is not in `reached`, so the solver may go back to it and expand
+            // it again; neither counts
+            let mut dead_end = state != init_state;
             for next_state in self.next_states(&state) {
                 if reached.contains_key(&next_state) {
                     continue;
                 }
+                dead_end &= next_state == init_state;
                 reached.insert(next_state.clone(), state.clone());
                 let removed_state = self.remove_pieces(&next_state);
                 if next_state != removed_state {
@@ -143,6 +174,9 @@ impl Puzzle {
                 }
                 queue.push(Reverse((removed_state.clone(), step + 1)));
             }
+            if dead_end {
+                dead_ends += 1;
+            }
         }
         if log {
             println!("INFO: FAIL whole missing solution");
@@ -151,6 +185,7 @@ impl Puzzle {
             ok: false,
             step: None,
             reached,
+            dead_ends,
             end_state: None,
         }
     }
@@ -253,6 +288,36 @@ impl Puzzle {
             multi: None,
         }
     }
+    /// Like `base` for the cells of `shape`; cells outside of it are holes
+    pub fn base_in(shape: &Cells, num_pieces: usize, limit: Option<usize>) -> Puzzle {
+        let size = shape.size;
+        let snake = |&V3(x, y, z): &V3| {
+            let py = if z % 2 == 0 { y } else { size - y - 1 };
+            let px = if (y + z * size).is_multiple_of(2) {
+                x
+            } else {
+                size - x - 1
+            };
+            (z * size + py) * size + px
+        };
+        let mut order = V3Iter::cube(size).filter(|&v| shape.getv(v)).collect_vec();
+        order.sort_by_key(snake);
+        let chunk = order.len().div_ceil(num_pieces);
+        let mut blocks = vec![Cells::empty(size); num_pieces];
+        for (k, v) in order.into_iter().enumerate() {
+            blocks[k / chunk].setv(v, true);
+        }
+        // skipped cells can split a piece
+        merge_stray_parts(&mut blocks);
+        Puzzle {
+            pieces: blocks.iter().map(Piece::from_block).collect(),
+            size,
+            margin: size,
+            space: size * 5,
+            reach_limit: limit,
+            multi: None,
+        }
+    }
     pub fn to_str(&self) -> String {
         let mut s = String::new();
         for (i, piece) in self.pieces.iter().enumerate() {
@@ -370,6 +435,26 @@ burr_plate([[\n",
         };
         crate::iters::SubsetsIter::new(&available, use_pieces)
     }
+    /// Direction in which the piece slides out without meeting the cells of another piece,
+    /// preferring up; None if every straight path is blocked, the solver removes pieces once
+    /// their bounding boxes are apart (`positions` are offsets of the pieces, None for removed
+    /// pieces)
+    pub fn removal_direction(&self, positions: &[Option<V3I>], i: usize) -> Option<V3I> {
+        let cells = |k: usize, p: V3I| self.pieces[k].sparse.iter().map(move |&x| V3I::from(x) + p);
+        let others: std::collections::HashSet<V3I> = positions
+            .iter()
+            .enumerate()
+            .filter(|&(k, _)| k != i)
+            .filter_map(|(k, p)| p.map(|p| (k, p)))
+            .flat_map(|(k, p)| cells(k, p))
+            .collect();
+        let own = cells(i, positions[i].unwrap()).collect_vec();
+        // the offsets stay within the space, so this far every piece is passed
+        let far = self.space + self.size;
+        [D6[4], D6[0], D6[2], D6[1], D6[3], D6[5]]
+            .into_iter()
+            .find(|&d| (1..=far).all(|s| own.iter().all(|&x| !others.contains(&(x + d * s)))))
+    }
     pub fn check_puzzle(&self) -> bool {
         for i in 0..self.pieces.len() {
             if self.pieces[i].block.count() == 0 {
@@ -396,6 +481,7 @@ burr_plate([[\n",
 #[derive(Clone, Debug)]
 pub enum Move {
     Shift(Vec<usize>, V3I),
+    /// Direction of `Puzzle::removal_direction`, zero if the piece cannot slide out straight
     Remove(usize, V3I),
 }
 #[derive(Clone, Debug)]
@@ -405,6 +491,15 @@ pub enum ShrinkMove {
 }
 
 impl SolveResult {
+    /// Number of states visited by the search
+    pub fn states(&self) -> usize {
+        self.reached.len()
+    }
+    /// Expanded states from which the solver found no new state; states left in the queue,
+    /// e.g. at the reach limit, are not dead ends
+    pub fn dead_ends(&self) -> usize {
+        self.dead_ends
+    }
     pub fn moves(&self, puzzle: &Puzzle) -> Vec<Move> {
         let mut moves = Vec::new();
         let mut end_state = self.end_state.clone().unwrap();
@@ -438,7 +533,18 @@ impl SolveResult {
                             ),
                         ));
                     } else {
-                        moves.push(Move::Remove(i, V3I(px, py, pz)))
+                        let positions = prev_state
+                            .indexes
+                            .iter()
+                            .map(|index| {
+                                index.map(|index| {
+                                    V3I::from(Cells::from_index(puzzle.space, index))
+                                        - prev_state.shift
+                                })
+                            })
+                            .collect_vec();
+                        let d = puzzle.removal_direction(&positions, i);
+                        moves.push(Move::Remove(i, d.unwrap_or(V3I(0, 0, 0))))
                     }
                 }
             }
@@ -654,6 +760,40 @@ mod tests {
         assert!(!result.ok);
     }
     #[test]
+    fn solver_dead_end() {
+        // the cell piece goes +y, +z, +x out of the maze; +x first is a pocket
+        let piece_a = Piece::from_str(
+            4,
+            "
+            ....|....|....|....
+            ....|.X..|....|....
+            ....|....|....|....
+            ....|....|....|....",
+        );
+        let piece_b = Piece::from_str(
+            4,
+            "
+            XXXX|XXXX|XXXX|XXXX
+            XXXX|X..X|XXXX|XXXX
+            XXXX|X.XX|X...|XXXX
+            XXXX|XXXX|XXXX|XXXX",
+        );
+        let mut puzzle = Puzzle {
+            pieces: vec![piece_a, piece_b],
+            size: 4,
+            margin: 4,
+            space: 20,
+            reach_limit: None,
+            multi: None,
+        };
+        let result = puzzle.solve();
+        assert_eq!(result.step, Some(3));
+        assert_eq!(result.dead_ends(), 1);
+        // states left in the queue are not dead ends
+        puzzle.reach_limit = Some(1);
+        assert_eq!(puzzle.solve().dead_ends(), 0);
+    }
+    #[test]
     fn solver_step() {
         let piece_a = Piece::from_str(
             3,
@@ -829,6 +969,55 @@ XXXX|XXXX|XXXX|XXXX",
         assert_eq!(shrink.len(), 8);
     }
     #[test]
+    fn removal_direction_checks_cells() {
+        // the center cell, boxed in by six cells whose bounding boxes are apart from it
+        let mut puzzle = Puzzle::base(3, 7, 0, None);
+        let cells = [V3(1, 1, 1)].into_iter().chain(
+            D6.iter()
+                .map(|&d| (V3I(1, 1, 1) + d).into_v3_in(&V3::cube(3)).unwrap()),
+        );
+        puzzle.pieces = cells
+            .map(|x| {
+                let mut block = Cells::empty(3);
+                block.setv(x, true);
+                Piece::from_block(&block)
+            })
+            .collect();
+        let mut positions = vec![Some(V3I(0, 0, 0)); 7];
+        assert_eq!(puzzle.removal_direction(&positions, 0), None);
+        // up is preferred, then the first open side
+        assert_eq!(puzzle.removal_direction(&positions, 5), Some(D6[4]));
+        positions[2] = None;
+        assert_eq!(puzzle.removal_direction(&positions, 0), Some(D6[1]));
+
+        let puzzle = crate::server::sample_puzzle();
+        let result = puzzle.solve();
+        let moves = result.moves(&puzzle);
+        assert!(moves
+            .iter()
+            .all(|mov| !matches!(mov, Move::Remove(_, d) if *d == V3I(0, 0, 0))));
+    }
+    #[test]
+    fn test_base_in() {
+        // U shape: skipping the gap splits the snake order of a piece
+        let shape = Piece::from_str(
+            3,
+            "
+            xxx|xxx|xxx
+            x.x|x.x|x.x
+            x.x|x.x|x.x",
+        )
+        .block;
+        let puzzle = Puzzle::base_in(&shape, 3, None);
+        assert!(puzzle.check_puzzle());
+        let mut union = Cells::empty(3);
+        for piece in puzzle.pieces.iter() {
+            union.or_inplace(&piece.block);
+        }
+        assert_eq!(union.count(), shape.count());
+        assert!(!union.overlap(&Piece::from_str(3, "...|...|...\n.x.|.x.|.x.\n.x.|.x.|.x.").block));
+    }
+    #[test]
     fn test_subset_pieces() {
         let mut puzzle = Puzzle::base(3, 4, 1, None);
         puzzle.multi = Some(1);
@@ -1,3 +1,4 @@
+use itertools::Itertools;
 use once_cell::sync::Lazy;
 use std::collections::{HashMap, HashSet};
 
@@ -8,6 +9,9 @@ use crate::{
     v3::V3,
 };
 
+/// Largest piece count packed two cells per byte
+const NIBBLE_PIECES: usize = 15;
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct PuzzleNumFormat {
     size: V3,
@@ -75,6 +79,45 @@ impl PuzzleNumFormat {
             .collect();
         Self::new(size, piece, cells)
     }
+    /// Size, piece count, then two cells per byte, or one above `NIBBLE_PIECES` pieces
+    pub fn to_packed(&self) -> Result<Vec<u8>, String> {
+        let V3(sx, sy, sz) = self.size;
+        let byte = |n: usize| u8::try_from(n).map_err(|_| format!("{} does not fit a byte", n));
+        let mut bytes = vec![byte(sx)?, byte(sy)?, byte(sz)?, byte(self.piece)?];
+        if let Some(&label) = self.cells.iter().find(|&&c| c > self.piece) {
+            return Err(format!("cell label {} above {} pieces", label, self.piece));
+        }
+        if self.piece > NIBBLE_PIECES {
+            bytes.extend(self.cells.iter().map(|&c| c as u8));
+            return Ok(bytes);
+        }
+        for pair in self.cells.chunks(2) {
+            let high = pair.get(1).copied().unwrap_or(0);
+            bytes.push((pair[0] | high << 4) as u8);
+        }
+        Ok(bytes)
+    }
+    pub fn from_packed(bytes: &[u8]) -> Result<Self, String> {
+        if bytes.len() < 4 {
+            return Err("truncated header".to_owned());
+        }
+        let size = V3(bytes[0] as usize, bytes[1] as usize, bytes[2] as usize);
+        let piece = bytes[3];
+        let len = size.0 * size.1 * size.2;
+        let data = &bytes[4..];
+        let wide = piece as usize > NIBBLE_PIECES;
+        if data.len() != if wide { len } else { len.div_ceil(2) } {
+            return Err(format!("{} bytes for {} cells", data.len(), len));
+        }
+        let cells = if wide {
+            data.iter().map(|&c| c as usize).collect()
+        } else {
+            (0..len)
+                .map(|i| (data[i / 2] >> (i % 2 * 4) & 0xf) as usize)
+                .collect()
+        };
+        Ok(Self::new(size, piece as usize, cells))
+    }
     fn rotate(&self, rot: &V3Matrix) -> Self {
         let mut cells = self.clone();
         for x in V3Iter::new(self.size) {
@@ -92,24 +135,35 @@ impl PuzzleNumFormat {
         }
         cells
     }
-    fn rotate_all(&self) -> Vec<Self> {
+    fn rotate_all(&self, mirror: bool) -> Vec<Self> {
         let mut result = Vec::new();
-        use itertools::Itertools;
         for mut index in (1..self.piece + 1).permutations(self.piece) {
             index.insert(0, 0);
             let rot_index = self.rotate_index(&index);
-            for rot in V3Matrix::rot_all(self.size.0).iter() {
+            for rot in V3Matrix::rot_all(self.size.0, mirror).iter() {
                 result.push(rot_index.rotate(rot));
             }
         }
         result
     }
+    /// Canonical form under the 24 proper rotations
     pub fn normalize(&self) -> Self {
-        self.rotate_all()
+        self.normalize_with(false)
+    }
+    /// Canonical form, also under the 24 improper rotations if `mirror`
+    pub fn normalize_with(&self, mirror: bool) -> Self {
+        self.rotate_all(mirror)
             .into_iter()
             .min_by_key(|format| format.to_block_code())
             .unwrap()
     }
+    pub fn mirror(&self) -> Self {
+        self.rotate(&V3Matrix::mirror_x(self.size.0))
+    }
+    /// Mirror image is not reachable by proper rotations
+    pub fn is_chiral(&self) -> bool {
+        self.normalize() != self.mirror().normalize()
+    }
     pub fn is_connected(&self) -> bool {
         self.to_puzzle()
             .pieces
@@ -128,10 +182,13 @@ impl PuzzleNumFormat {
 struct V3Matrix {
     matrix: Vec<Vec<isize>>,
 }
-static ROT_ALL: Lazy<HashMap<usize, Vec<V3Matrix>>> = Lazy::new(|| {
+/// Rotations of every cube `Puzzle::base` and the voxelized shapes use
+static ROT_ALL: Lazy<HashMap<(usize, bool), Vec<V3Matrix>>> = Lazy::new(|| {
     let mut rots = HashMap::new();
-    for size in 2..=4 {
-        rots.insert(size, V3Matrix::rot_all_gen(size));
+    for size in 1..=9 {
+        for mirror in [false, true] {
+            rots.insert((size, mirror), V3Matrix::rot_all_gen(size, mirror));
+        }
     }
     rots
 });
@@ -207,10 +264,20 @@ impl V3Matrix {
             ],
         }
     }
-    fn rot_all(size: usize) -> &'static [Self] {
-        &ROT_ALL[&size]
+    fn mirror_x(size: usize) -> Self {
+        Self {
+            matrix: vec![
+                vec![-1, 0, 0, 0],
+                vec![0, 1, 0, 0],
+                vec![0, 0, 1, 0],
+                vec![size as isize - 1, 0, 0, 1],
+            ],
+        }
     }
-    fn rot_all_gen(size: usize) -> Vec<Self> {
+    fn rot_all(size: usize, mirror: bool) -> &'static [Self] {
+        &ROT_ALL[&(size, mirror)]
+    }
+    fn rot_all_gen(size: usize, mirror: bool) -> Vec<Self> {
         let mut matrixes = HashSet::new();
         let mut rot_x = V3Matrix::one();
         for _ in 0..4 {
@@ -225,8 +292,13 @@ impl V3Matrix {
             }
             rot_x = rot_x.mul(&V3Matrix::rot_x(size));
         }
+        if mirror {
+            let mirror_x = V3Matrix::mirror_x(size);
+            let mirrored = matrixes.iter().map(|m| m.mul(&mirror_x)).collect_vec();
+            matrixes.extend(mirrored);
+        }
         let matrixes = matrixes.into_iter().collect::<Vec<_>>();
-        assert_eq!(matrixes.len(), 24);
+        assert_eq!(matrixes.len(), if mirror { 48 } else { 24 });
         matrixes
     }
 }
@@ -235,6 +307,26 @@ impl V3Matrix {
 mod tests {
     use super::*;
     #[test]
+    fn test_packed() {
+        for code in ["222:2:01000200", "333:3:111222333111222333111222333"] {
+            let format = PuzzleNumFormat::from_block_code(code);
+            let packed = format.to_packed().unwrap();
+            assert_eq!(packed.len(), 4 + format.cells.len().div_ceil(2));
+            assert_eq!(PuzzleNumFormat::from_packed(&packed).unwrap(), format);
+        }
+        assert!(PuzzleNumFormat::from_packed(&[2, 2, 2, 2, 0]).is_err());
+        // one byte per cell above 15 pieces
+        let cells = (0..27).map(|i| i % 17).collect_vec();
+        let many = PuzzleNumFormat::new(V3::cube(3), 16, cells);
+        let packed = many.to_packed().unwrap();
+        assert_eq!(packed.len(), 4 + 27);
+        assert_eq!(PuzzleNumFormat::from_packed(&packed).unwrap(), many);
+        let large = PuzzleNumFormat::new(V3(256, 1, 1), 1, vec![1; 256]);
+        assert!(large.to_packed().is_err());
+        let unlabelled = PuzzleNumFormat::new(V3::cube(2), 1, vec![2; 8]);
+        assert!(unlabelled.to_packed().is_err());
+    }
+    #[test]
     fn test_rotate() {
         let size = V3(2, 2, 2);
         let cells = vec![0, 1, 0, 0, 0, 2, 0, 0];
@@ -264,8 +356,21 @@ mod tests {
         let size = V3(2, 2, 2);
         let cells = vec![0, 1, 0, 0, 0, 2, 0, 0];
         let format = PuzzleNumFormat::new(size, 2, cells);
-        let formats = format.rotate_all();
+        let formats = format.rotate_all(false);
         assert_eq!(formats.len(), 24 * 2);
+        let formats = format.rotate_all(true);
+        assert_eq!(formats.len(), 48 * 2);
+    }
+    #[test]
+    fn test_normalize_sizes() {
+        for size in [1, 5, 9] {
+            let mut cells = vec![0; size * size * size];
+            cells[size * size * size / 3] = 1;
+            let format = PuzzleNumFormat::new(V3::cube(size), 1, cells);
+            let normalized = format.normalize();
+            assert_eq!(normalized, format.mirror().normalize());
+            assert_eq!(normalized.cells.iter().filter(|&&c| c == 1).count(), 1);
+        }
     }
     #[test]
     fn test_puzzle_num_format() {
@@ -298,7 +403,26 @@ mod tests {
         assert_eq!(rot_x, V3Matrix::one());
         assert_eq!(rot_y, V3Matrix::one());
         assert_eq!(rot_z, V3Matrix::one());
-        let matrixes = V3Matrix::rot_all(2);
+        let matrixes = V3Matrix::rot_all(2, false);
         assert_eq!(matrixes.len(), 24);
+        let matrixes = V3Matrix::rot_all(2, true);
+        assert_eq!(matrixes.len(), 48);
+    }
+    #[test]
+    fn test_chiral() {
+        let size = V3(2, 2, 2);
+        // screw tetracube: not superimposable on its mirror image
+        let cells = vec![1, 1, 0, 1, 0, 0, 0, 1];
+        let format = PuzzleNumFormat::new(size, 1, cells);
+        assert!(format.is_chiral());
+        assert_ne!(format.normalize(), format.mirror().normalize());
+        assert_eq!(
+            format.normalize_with(true),
+            format.mirror().normalize_with(true)
+        );
+        // L tromino is flat, so it is its own mirror image
+        let cells = vec![1, 1, 0, 1, 0, 0, 0, 0];
+        let format = PuzzleNumFormat::new(size, 1, cells);
+        assert!(!format.is_chiral());
     }
 }
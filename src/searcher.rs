@@ -1,20 +1,115 @@
-use crate::{cells::Cells, launcher::PuzzleWriter, puzzle::*};
+use crate::{
+    archive::SolveStats,
+    cells::Cells,
+    checkpoint::{ChainJson, Checkpoint, Checkpointer, CHECKPOINT_VERSION},
+    island::Island,
+    launcher::PuzzleWriter,
+    pareto::ParetoFront,
+    puzzle::*,
+    puzzle_num_format::PuzzleNumFormat,
+    solve_cache::SolveCache,
+    strategy::{HillClimb, SearchStrategy},
+    v3::V3,
+};
 use itertools::Itertools;
+use rand::Rng;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use std::{
+    collections::{HashMap, VecDeque},
     fmt::Debug,
     sync::{mpsc::Sender, Arc},
 };
 
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct VisitedStats {
+    pub hits: usize,
+    pub misses: usize,
+}
+
+/// Values of the latest `capacity` designs, keyed by normalized block code
+#[derive(Debug)]
+pub struct VisitedCache<V> {
+    capacity: usize,
+    values: HashMap<String, Option<V>>,
+    order: VecDeque<String>,
+    pub stats: VisitedStats,
+}
+impl<V: Copy> VisitedCache<V> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            values: HashMap::new(),
+            order: VecDeque::new(),
+            stats: VisitedStats::default(),
+        }
+    }
+    /// Cached value of an equivalent design, else `evaluate` which is then remembered
+    pub fn get_or_insert_with(
+        &mut self,
+        puzzle: &Puzzle,
+        evaluate: impl FnOnce() -> Option<V>,
+    ) -> Option<V> {
+        if self.capacity == 0 {
+            self.stats.misses += 1;
+            return evaluate();
+        }
+        let code = PuzzleNumFormat::from_puzzle(puzzle)
+            .normalize()
+            .to_block_code();
+        if let Some(&value) = self.values.get(&code) {
+            self.stats.hits += 1;
+            return value;
+        }
+        self.stats.misses += 1;
+        let value = evaluate();
+        if self.order.len() >= self.capacity {
+            let oldest = self.order.pop_front().unwrap();
+            self.values.remove(&oldest);
+        }
+        self.values.insert(code.clone(), value);
+        self.order.push_back(code);
+        value
+    }
+}
+
+/// Random source of every generator and strategy, reproducible from its seed
+pub type SearchRng = rand_chacha::ChaCha8Rng;
+
+/// What `Launcher` runs on each thread
+pub trait Searcher: Clone + Send + Sync + Debug {
+    type Evaluator: Evaluator;
+    fn evaluator(&self) -> &Self::Evaluator;
+    /// Sends every new best puzzle to `logger` and returns the last one
+    fn search(&self, logger: Option<Sender<Puzzle>>, rnd: &mut SearchRng) -> Puzzle;
+    /// `search` of one `Launcher` thread, exchanging puzzles through `island`
+    /// and saving its chains to `checkpointer`
+    fn search_thread(
+        &self,
+        logger: Option<Sender<Puzzle>>,
+        rnd: &mut SearchRng,
+        island: Option<&Island<<Self::Evaluator as Evaluator>::Value>>,
+        checkpointer: Option<&mut Checkpointer<<Self::Evaluator as Evaluator>::Value>>,
+    ) -> Puzzle;
+    fn cache(&self) -> Option<&SolveCache> {
+        None
+    }
+}
+
 #[derive(Debug, Clone)]
-pub struct PuzzleSearcher<G: PuzzleGenerator, E: Evaluator> {
+pub struct PuzzleSearcher<G: PuzzleGenerator, E: Evaluator, S: SearchStrategy<E::Value> = HillClimb>
+{
     tries: usize,
-    stack: usize,
-    give_up: usize,
     initial: Puzzle,
     generator: G,
     pub evaluator: E,
+    pub strategy: S,
+    /// Capacity of the `VisitedCache`, 0 to solve every candidate
+    visited: usize,
+    /// Shared with other threads and runs, consulted on `VisitedCache` misses
+    cache: Option<Arc<SolveCache>>,
 }
 impl<G: PuzzleGenerator, E: Evaluator> PuzzleSearcher<G, E> {
+    /// Hill-climbs `stack` independent chains
     pub fn new(
         tries: usize,
         stack: usize,
@@ -25,77 +120,239 @@ impl<G: PuzzleGenerator, E: Evaluator> PuzzleSearcher<G, E> {
     ) -> PuzzleSearcher<G, E> {
         PuzzleSearcher {
             tries,
-            stack,
             initial,
-            give_up,
             generator,
             evaluator,
+            strategy: HillClimb { stack, give_up },
+            visited: 10000,
+            cache: None,
         }
     }
-    pub fn search(&self, logger: Option<Sender<Puzzle>>) -> Puzzle {
-        let init_puzzle = self.initial.clone();
-        let mut best_puzzles = vec![(init_puzzle, E::Value::default(), vec![], 0); self.stack];
-        for i in 0..self.tries {
-            for k in 0..self.stack {
-                {
-                    let best = &mut best_puzzles[k];
-                    best.3 += 1;
-                    if best.3 > self.give_up {
-                        return best_puzzles[0].0.to_owned();
+}
+impl<G: PuzzleGenerator, E: Evaluator, S: SearchStrategy<E::Value>> PuzzleSearcher<G, E, S> {
+    pub fn with_strategy<T: SearchStrategy<E::Value>>(
+        self,
+        strategy: T,
+    ) -> PuzzleSearcher<G, E, T> {
+        PuzzleSearcher {
+            tries: self.tries,
+            initial: self.initial,
+            generator: self.generator,
+            evaluator: self.evaluator,
+            strategy,
+            visited: self.visited,
+            cache: self.cache,
+        }
+    }
+    pub fn with_cache(mut self, cache: Arc<SolveCache>) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+    pub fn with_visited(mut self, capacity: usize) -> Self {
+        self.visited = capacity;
+        self
+    }
+    fn log_try(&self, i: usize, state: &S::State, stats: &VisitedStats) {
+        println!(
+            "try #{} {} visited {}/{}",
+            i,
+            self.strategy.describe(state),
+            stats.hits,
+            stats.hits + stats.misses
+        );
+    }
+    pub fn search(&self, logger: Option<Sender<Puzzle>>, rnd: &mut SearchRng) -> Puzzle {
+        self.search_inspect(logger, rnd, |i, state, stats| self.log_try(i, state, stats))
+    }
+    /// Exchanges the best puzzle with the other islands every `Migration::interval` tries,
+    /// and continues from the checkpoint of `checkpointer` if any
+    pub fn search_thread(
+        &self,
+        logger: Option<Sender<Puzzle>>,
+        rnd: &mut SearchRng,
+        island: Option<&Island<E::Value>>,
+        checkpointer: Option<&mut Checkpointer<E::Value>>,
+    ) -> Puzzle {
+        self.run(logger, rnd, island, checkpointer, |i, state, stats| {
+            self.log_try(i, state, stats)
+        })
+    }
+    /// `inspect` sees the strategy state after every try
+    pub fn search_inspect(
+        &self,
+        logger: Option<Sender<Puzzle>>,
+        rnd: &mut SearchRng,
+        inspect: impl FnMut(usize, &S::State, &VisitedStats),
+    ) -> Puzzle {
+        self.run(logger, rnd, None, None, inspect)
+    }
+    fn run(
+        &self,
+        logger: Option<Sender<Puzzle>>,
+        rnd: &mut SearchRng,
+        island: Option<&Island<E::Value>>,
+        mut checkpointer: Option<&mut Checkpointer<E::Value>>,
+        mut inspect: impl FnMut(usize, &S::State, &VisitedStats),
+    ) -> Puzzle {
+        let mut state = self.strategy.init(&self.initial);
+        let mut visited = VisitedCache::new(self.visited);
+        let mut best: Option<(Puzzle, E::Value)> = None;
+        let mut start = 0;
+        if let Some(checkpoint) = checkpointer.as_deref_mut().and_then(Checkpointer::take) {
+            let chains = checkpoint
+                .chains
+                .iter()
+                .map(|chain| chain.to_chain(&self.initial))
+                .collect();
+            best = checkpoint.best.map(|best| {
+                let chain = best.to_chain(&self.initial);
+                (chain.puzzle, chain.value)
+            });
+            let best_value = best.as_ref().map(|(_, value)| *value);
+            state = self.strategy.restore(&self.initial, chains, best_value);
+            start = checkpoint.tries;
+            *rnd = checkpoint.rng;
+        }
+        let mut sent = ParetoFront::default();
+        if let Some((_, value)) = best.as_ref() {
+            sent.insert((), *value);
+        }
+        for i in start..self.tries {
+            let candidates = self
+                .strategy
+                .parents(&state)
+                .iter()
+                .map(|puzzle| {
+                    let new_puzzle = self.generator.generate(puzzle, rnd);
+                    let value = visited.get_or_insert_with(&new_puzzle, || {
+                        if let Some(cache) = self.cache.as_ref() {
+                            return cache.value(&new_puzzle, &self.evaluator);
+                        }
+                        let result = new_puzzle.solve();
+                        result
+                            .ok
+                            .then(|| self.evaluator.evaluate(&new_puzzle, &result))
+                    });
+                    (new_puzzle, value)
+                })
+                .collect_vec();
+            let improvements = self.strategy.improvements(&state, &candidates);
+            for ((puzzle, value), improved) in candidates.iter().zip(improvements) {
+                let Some(value) = value else {
+                    continue;
+                };
+                println!("#{} value: {}", i, value.to_str());
+                // the best one alone unless the values are only partially ordered, and what
+                // improves a chain of the strategy
+                if sent.insert((), *value) || improved {
+                    if let Some(tx) = logger.as_ref() {
+                        tx.send(puzzle.to_owned()).expect("failed to send puzzle");
                     }
                 }
-
-                let (puzzle, best_value, _, _) = &best_puzzles[k];
-                let new_puzzle = self.generator.generate(&puzzle);
-                //println!("to_solve\n{}", new_puzzle.to_str());
-                let result = new_puzzle.solve();
-                if result.ok {
-                    let value = self.evaluator.evaluate(&new_puzzle, &result);
-                    println!("#{} value: {}", i, value.to_str());
-                    if best_value <= &value {
-                        let shrink_moves = result.shrink_move(&result.moves(&new_puzzle));
-                        let count = if best_value < &value {
-                            logger.as_ref().map(|tx| {
-                                tx.send(new_puzzle.to_owned())
-                                    .expect("failed to send puzzle");
-                            });
-                            println!("#{} updated", i);
-                            0
-                        } else {
-                            best_puzzles[k].3
-                        };
-                        best_puzzles[k] = (new_puzzle, value, shrink_moves, count);
-                    }
+                if best.as_ref().is_none_or(|(_, best)| best < value) {
+                    println!("#{} updated", i);
+                    best = Some((puzzle.clone(), *value));
+                }
+            }
+            let go_on = self
+                .strategy
+                .update(&mut state, i, self.tries, candidates, rnd);
+            if let Some(island) = island {
+                let best = best.as_ref().map(|(puzzle, value)| (puzzle, *value));
+                for migrant in island.migrate(i, best, rnd) {
+                    println!("#{} migrant: {}", i, migrant.1.to_str());
+                    self.strategy.immigrate(&mut state, migrant);
                 }
-                if i % 1 == 0 {
-                    println!(
-                        "try #{}-({}<{}) ({})",
-                        i,
-                        best_puzzles[k].3,
-                        self.give_up,
-                        best_puzzles[k].1.to_str(),
-                    );
+            }
+            inspect(i, &state, &visited.stats);
+            if let Some(checkpointer) = checkpointer.as_deref() {
+                if go_on && checkpointer.due(i + 1) {
+                    let checkpoint = Checkpoint {
+                        version: CHECKPOINT_VERSION,
+                        tries: i + 1,
+                        chains: self
+                            .strategy
+                            .chains(&state)
+                            .iter()
+                            .map(ChainJson::from_chain)
+                            .collect(),
+                        best: best
+                            .as_ref()
+                            .map(|(puzzle, value)| ChainJson::new(puzzle, *value, 0)),
+                        rng: rnd.clone(),
+                    };
+                    checkpointer.save(&checkpoint).unwrap();
                 }
             }
+            if !go_on {
+                break;
+            }
         }
-        best_puzzles[0].0.to_owned()
+        best.map_or_else(|| self.initial.clone(), |(puzzle, _)| puzzle)
+    }
+}
+impl<G: PuzzleGenerator, E: Evaluator, S: SearchStrategy<E::Value>> Searcher
+    for PuzzleSearcher<G, E, S>
+{
+    type Evaluator = E;
+    fn evaluator(&self) -> &E {
+        &self.evaluator
+    }
+    fn search(&self, logger: Option<Sender<Puzzle>>, rnd: &mut SearchRng) -> Puzzle {
+        PuzzleSearcher::search(self, logger, rnd)
+    }
+    fn search_thread(
+        &self,
+        logger: Option<Sender<Puzzle>>,
+        rnd: &mut SearchRng,
+        island: Option<&Island<E::Value>>,
+        checkpointer: Option<&mut Checkpointer<E::Value>>,
+    ) -> Puzzle {
+        PuzzleSearcher::search_thread(self, logger, rnd, island, checkpointer)
+    }
+    fn cache(&self) -> Option<&SolveCache> {
+        self.cache.as_deref()
     }
 }
 
-pub trait EvalValue: Ord + Copy + Clone + Default + Send + Sync {
+pub trait EvalValue:
+    Ord + Copy + Clone + Default + Send + Sync + Debug + Serialize + DeserializeOwned
+{
     fn to_str(&self) -> String;
     fn to_path(&self) -> String;
+    /// Used as energy by annealing. Monotone with `Ord` only while the keys stay below 1000,
+    /// see `lexicographic`; larger values may tie.
+    fn to_scalar(&self) -> f64;
+    /// Kept as a front of trade-offs by the `Launcher`, see `dominates`
+    const PARETO: bool = false;
+    /// At least as good as `other` in every objective
+    fn dominates(&self, other: &Self) -> bool {
+        self >= other
+    }
+}
+
+/// Later keys count for 1/1000 of the previous one. Keys are clamped at 999, so values that
+/// differ only above it tie; a wider base would lose exactness in `f64` for four keys.
+pub fn lexicographic(keys: &[usize]) -> f64 {
+    keys.iter()
+        .enumerate()
+        .map(|(i, &key)| key.min(999) as f64 * 1000f64.powi(-(i as i32)))
+        .sum()
 }
 
 pub trait Evaluator: Clone + Send + Sync + Debug {
     type Value: EvalValue;
     fn evaluate(&self, puzzle: &Puzzle, result: &SolveResult) -> Self::Value;
+    /// Same value from cached stats, `None` if they are not enough
+    fn stats_value(&self, _stats: &SolveStats) -> Option<Self::Value> {
+        None
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct ShrinkStepEvaluator {}
-#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Ord, PartialOrd)]
-pub struct ShrinkStepValue(usize, usize, usize);
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Serialize, Deserialize)]
+pub struct ShrinkStepValue(pub usize, pub usize, pub usize);
 impl EvalValue for ShrinkStepValue {
     fn to_str(&self) -> String {
         format!("first={} shrink={} all={}", self.0, self.1, self.2)
@@ -103,6 +360,10 @@ impl EvalValue for ShrinkStepValue {
     fn to_path(&self) -> String {
         format!("F{}S{}A{}", self.0, self.1, self.2)
     }
+    /// One unit per first removal step
+    fn to_scalar(&self) -> f64 {
+        lexicographic(&[self.0, self.1, self.2])
+    }
 }
 
 impl Evaluator for ShrinkStepEvaluator {
@@ -113,11 +374,18 @@ impl Evaluator for ShrinkStepEvaluator {
         let first = first_remove(&shrink_moves);
         ShrinkStepValue(first, shrink_moves.len(), moves.len())
     }
+    fn stats_value(&self, stats: &SolveStats) -> Option<Self::Value> {
+        Some(ShrinkStepValue(
+            stats.first as usize,
+            stats.shrink as usize,
+            stats.all as usize,
+        ))
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct DupDropEvaluator {}
-#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Ord, PartialOrd)]
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Serialize, Deserialize)]
 pub struct DupDropValue(usize, usize, usize, usize);
 impl EvalValue for DupDropValue {
     fn to_str(&self) -> String {
@@ -129,6 +397,9 @@ impl EvalValue for DupDropValue {
     fn to_path(&self) -> String {
         format!("F{}D{}S{}A{}", self.0, self.1, self.2, self.3)
     }
+    fn to_scalar(&self) -> f64 {
+        lexicographic(&[self.0, self.1, self.2, self.3])
+    }
 }
 impl Evaluator for DupDropEvaluator {
     type Value = DupDropValue;
@@ -142,15 +413,36 @@ impl Evaluator for DupDropEvaluator {
 }
 
 pub trait PuzzleGenerator: Clone + Send + Sync + Debug {
-    fn generate(&self, puzzle: &Puzzle) -> Puzzle;
+    fn generate(&self, puzzle: &Puzzle, rnd: &mut impl Rng) -> Puzzle;
 }
 
+/// Swaps before giving the puzzle back unchanged, when no walk meets the constraints
+const ATTEMPTS: usize = 100000;
+
 #[derive(Clone, Debug)]
 pub struct SwapPuzzleGenerator<C: PuzzleConstraints> {
     pub constraints: C,
 }
 impl<C: PuzzleConstraints> PuzzleGenerator for SwapPuzzleGenerator<C> {
-    fn generate(&self, puzzle: &Puzzle) -> Puzzle {
+    fn generate(&self, puzzle: &Puzzle, rnd: &mut impl Rng) -> Puzzle {
+        let size = puzzle.size;
+        self.generate_at(puzzle, rnd, |rnd| {
+            V3(
+                rnd.gen_range(0..size),
+                rnd.gen_range(0..size),
+                rnd.gen_range(0..size),
+            )
+        })
+    }
+}
+impl<C: PuzzleConstraints> SwapPuzzleGenerator<C> {
+    /// Like `generate`, with the moved cell picked by `pick` at each retry
+    pub fn generate_at<R: Rng>(
+        &self,
+        puzzle: &Puzzle,
+        rnd: &mut R,
+        mut pick: impl FnMut(&mut R) -> V3,
+    ) -> Puzzle {
         let init_blocks = puzzle
             .pieces
             .iter()
@@ -158,13 +450,8 @@ impl<C: PuzzleConstraints> PuzzleGenerator for SwapPuzzleGenerator<C> {
             .collect_vec();
         let mut blocks = init_blocks.clone();
 
-        use rand::Rng;
-        let mut rnd = rand::thread_rng();
-
-        'retry: loop {
-            let x = rnd.gen_range(0..puzzle.size);
-            let y = rnd.gen_range(0..puzzle.size);
-            let z = rnd.gen_range(0..puzzle.size);
+        'retry: for _ in 0..ATTEMPTS {
+            let V3(x, y, z) = pick(rnd);
 
             let mut found = false;
             for a in 0..blocks.len() {
@@ -202,14 +489,15 @@ impl<C: PuzzleConstraints> PuzzleGenerator for SwapPuzzleGenerator<C> {
             if !self.constraints.is_ok(&blocks) {
                 continue 'retry;
             }
-            break;
+            let mut puzzle = puzzle.clone();
+            puzzle.pieces = blocks
+                .iter()
+                .map(|block| Piece::from_block(block))
+                .collect();
+            return puzzle;
         }
-        let mut puzzle = puzzle.clone();
-        puzzle.pieces = blocks
-            .iter()
-            .map(|block| Piece::from_block(block))
-            .collect();
-        puzzle
+        println!("WARN: no swap meets the constraints, the puzzle is kept");
+        puzzle.clone()
     }
 }
 
@@ -219,19 +507,39 @@ pub struct SwapNPuzzleGenerator<C: PuzzleConstraints> {
     pub constraints: C,
 }
 impl<C: PuzzleConstraints> PuzzleGenerator for SwapNPuzzleGenerator<C> {
-    fn generate(&self, puzzle: &Puzzle) -> Puzzle {
+    fn generate(&self, puzzle: &Puzzle, rnd: &mut impl Rng) -> Puzzle {
         let mut puzzle = puzzle.clone();
         for _ in 0..self.swaps {
             puzzle = SwapPuzzleGenerator {
                 constraints: self.constraints.clone(),
             }
-            .generate(&puzzle);
+            .generate(&puzzle, rnd);
         }
         puzzle
     }
 }
 
-trait PuzzleConstraints: Clone + Send + Sync + Debug {
+/// `restart` instead of `generator` at a rate of `rate`, e.g. a whole new partition
+#[derive(Clone, Debug)]
+pub struct RestartPuzzleGenerator<G: PuzzleGenerator, R: PuzzleGenerator> {
+    pub generator: G,
+    pub restart: R,
+    pub rate: f64,
+}
+impl<G: PuzzleGenerator, R: PuzzleGenerator> PuzzleGenerator for RestartPuzzleGenerator<G, R> {
+    fn generate(&self, puzzle: &Puzzle, rnd: &mut impl Rng) -> Puzzle {
+        // no draw at rate 0, so that runs without restarts keep their random sequence
+        if self.rate > 0.0 && rnd.gen_bool(self.rate.min(1.0)) {
+            self.restart.generate(puzzle, rnd)
+        } else {
+            self.generator.generate(puzzle, rnd)
+        }
+    }
+}
+
+/// Design rules on the pieces, checked on every generated puzzle.
+/// Library and And / Or / Not in `constraints`.
+pub trait PuzzleConstraints: Clone + Send + Sync + Debug {
     fn is_ok(&self, blocks: &[Cells]) -> bool;
 }
 #[derive(Clone, Debug)]
@@ -244,6 +552,27 @@ impl<C: PuzzleConstraints> PuzzleConstraints for MinPuzzleSizeConstraints<C> {
         blocks.iter().all(|block| block.count() >= self.size) && self.next.is_ok(blocks)
     }
 }
+/// Keeps every piece inside the target shape
+#[derive(Clone, Debug)]
+pub struct ShapePuzzleConstraints<C: PuzzleConstraints> {
+    pub shape: Cells,
+    pub next: C,
+}
+impl<C: PuzzleConstraints> PuzzleConstraints for ShapePuzzleConstraints<C> {
+    fn is_ok(&self, blocks: &[Cells]) -> bool {
+        blocks.iter().all(|block| {
+            let mut rest = block.clone();
+            rest.and_not_inplace(&self.shape);
+            rest.count() == 0
+        }) && self.next.is_ok(blocks)
+    }
+}
+/// Every constraint of the list holds
+impl<C: PuzzleConstraints> PuzzleConstraints for Vec<C> {
+    fn is_ok(&self, blocks: &[Cells]) -> bool {
+        self.iter().all(|c| c.is_ok(blocks))
+    }
+}
 #[derive(Clone, Debug)]
 pub struct TerminalPuzzleConstraints {}
 impl PuzzleConstraints for TerminalPuzzleConstraints {
@@ -252,7 +581,7 @@ impl PuzzleConstraints for TerminalPuzzleConstraints {
     }
 }
 
-fn first_remove(moves: &[ShrinkMove]) -> usize {
+pub fn first_remove(moves: &[ShrinkMove]) -> usize {
     for i in 0..moves.len() {
         if let ShrinkMove::Remove(_, _) = moves[i] {
             return i;
@@ -261,7 +590,7 @@ fn first_remove(moves: &[ShrinkMove]) -> usize {
     unreachable!("puzzle is not solved")
 }
 
-fn drop_count(moves: &[ShrinkMove]) -> usize {
+pub fn drop_count(moves: &[ShrinkMove]) -> usize {
     let mut drops = 0;
     let mut touch_pieces: Vec<usize> = vec![];
     let mut touch_count = 0;
@@ -284,6 +613,9 @@ fn drop_count(moves: &[ShrinkMove]) -> usize {
 
 #[cfg(test)]
 mod tests {
+    use rand::SeedableRng;
+
+    use crate::strategy::{Annealing, Beam, Restart, Schedule, Tabu};
     use crate::v3::V3I;
 
     use super::*;
@@ -299,7 +631,7 @@ mod tests {
             },
             ShrinkStepEvaluator {},
         );
-        let puzzle = searcher.search(None);
+        let puzzle = searcher.search(None, &mut SearchRng::seed_from_u64(0));
         println!("Found\n{}", puzzle.to_str());
         let result = puzzle.solve();
         assert!(result.ok);
@@ -308,6 +640,160 @@ mod tests {
         println!("Shrink #{} {:?}", shrink.len(), shrink);
     }
 
+    #[test]
+    fn puzzle_strategies() {
+        let searcher = PuzzleSearcher::new(
+            5,
+            1,
+            Puzzle::base(3, 4, 1, None),
+            10000,
+            SwapPuzzleGenerator {
+                constraints: TerminalPuzzleConstraints {},
+            },
+            ShrinkStepEvaluator {},
+        );
+        let mut rnd = SearchRng::seed_from_u64(0);
+        let annealing = searcher.clone().with_strategy(Annealing {
+            schedule: Schedule::Exponential {
+                start: 2.0,
+                rate: 0.9,
+            },
+            give_up: 100,
+        });
+        assert!(annealing.search(None, &mut rnd).solve().ok);
+        let restart = searcher.clone().with_strategy(Restart {
+            give_up: 1,
+            restarts: 1,
+        });
+        assert!(restart.search(None, &mut rnd).solve().ok);
+        let tabu = searcher.clone().with_strategy(Tabu {
+            neighbors: 2,
+            tenure: 4,
+            give_up: 100,
+        });
+        assert!(tabu.search(None, &mut rnd).solve().ok);
+        let beam = searcher.with_strategy(Beam {
+            width: 2,
+            branching: 2,
+            give_up: 100,
+        });
+        let mut widths = vec![];
+        let puzzle =
+            beam.search_inspect(None, &mut rnd, |_, state, _| widths.push(state.beam.len()));
+        assert!(puzzle.solve().ok);
+        assert!(widths.iter().all(|&w| w <= 2));
+    }
+
+    #[test]
+    fn stacked_chains_send_improvements() {
+        let searcher = PuzzleSearcher::new(
+            30,
+            3,
+            Puzzle::base(3, 4, 1, None),
+            10000,
+            SwapPuzzleGenerator {
+                constraints: TerminalPuzzleConstraints {},
+            },
+            ShrinkStepEvaluator {},
+        );
+        let (tx, rx) = std::sync::mpsc::channel();
+        let mut rnd = SearchRng::seed_from_u64(0);
+        let mut values = [ShrinkStepValue::default(); 3];
+        let mut improvements = 0;
+        searcher.search_inspect(Some(tx), &mut rnd, |_, state, _| {
+            for (value, chain) in values.iter_mut().zip(state.iter()) {
+                if *value < chain.value {
+                    improvements += 1;
+                    *value = chain.value;
+                }
+            }
+        });
+        // every strict improvement of each chain, not only of the best one
+        assert_eq!(rx.try_iter().count(), improvements);
+        assert!(improvements > 1);
+    }
+
+    #[test]
+    fn seeded_search() {
+        let searcher = PuzzleSearcher::new(
+            20,
+            2,
+            Puzzle::base(3, 4, 1, None),
+            10000,
+            SwapNPuzzleGenerator {
+                swaps: 2,
+                constraints: TerminalPuzzleConstraints {},
+            },
+            ShrinkStepEvaluator {},
+        );
+        let code = |seed| {
+            let puzzle = searcher.search(None, &mut SearchRng::seed_from_u64(seed));
+            PuzzleNumFormat::from_puzzle(&puzzle).to_block_code()
+        };
+        assert_eq!(code(1), code(1));
+    }
+
+    #[test]
+    fn resume_from_checkpoint() {
+        let dir = std::env::temp_dir().join(format!("resume_{}", std::process::id()));
+        let dir = dir.to_string_lossy().to_string();
+        let searcher = |tries| {
+            PuzzleSearcher::new(
+                tries,
+                2,
+                Puzzle::base(3, 4, 1, None),
+                10000,
+                SwapNPuzzleGenerator {
+                    swaps: 2,
+                    constraints: TerminalPuzzleConstraints {},
+                },
+                ShrinkStepEvaluator {},
+            )
+        };
+        let code = |puzzle: &Puzzle| PuzzleNumFormat::from_puzzle(puzzle).to_block_code();
+        let whole = searcher(20).search(None, &mut SearchRng::seed_from_u64(1));
+        // stopped after 10 tries, then continued with a fresh random source
+        let mut checkpointer = Checkpointer::open(&dir, 0, 5, false).unwrap();
+        let mut rnd = SearchRng::seed_from_u64(1);
+        searcher(10).search_thread(None, &mut rnd, None, Some(&mut checkpointer));
+        let mut checkpointer = Checkpointer::open(&dir, 0, 5, true).unwrap();
+        let mut rnd = SearchRng::seed_from_u64(2);
+        let resumed = searcher(20).search_thread(None, &mut rnd, None, Some(&mut checkpointer));
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(code(&resumed), code(&whole));
+    }
+
+    #[test]
+    fn visited_cache() {
+        let mut visited = VisitedCache::new(1);
+        let a = PuzzleNumFormat::from_block_code("222:2:11111122").to_puzzle();
+        // same design with the pieces numbered the other way
+        let b = PuzzleNumFormat::from_block_code("222:2:22222211").to_puzzle();
+        let c = PuzzleNumFormat::from_block_code("222:2:11111222").to_puzzle();
+        assert_eq!(visited.get_or_insert_with(&a, || Some(1)), Some(1));
+        assert_eq!(visited.get_or_insert_with(&b, || unreachable!()), Some(1));
+        assert_eq!(visited.get_or_insert_with(&c, || None), None);
+        // `a` was evicted
+        assert_eq!(visited.get_or_insert_with(&a, || Some(2)), Some(2));
+        assert_eq!(visited.stats, VisitedStats { hits: 1, misses: 3 });
+
+        let searcher = PuzzleSearcher::new(
+            20,
+            1,
+            Puzzle::base(2, 2, 0, None),
+            10000,
+            SwapPuzzleGenerator {
+                constraints: TerminalPuzzleConstraints {},
+            },
+            ShrinkStepEvaluator {},
+        );
+        let mut last = VisitedStats::default();
+        let mut rnd = SearchRng::seed_from_u64(0);
+        searcher.search_inspect(None, &mut rnd, |_, _, stats| last = *stats);
+        // only a handful of 2x2x2 designs with two pieces
+        assert!(last.hits > 0);
+    }
+
     #[test]
     fn puzzle_generator() {
         let holes = 5;
@@ -315,8 +801,9 @@ mod tests {
         let puzzle_generator = SwapPuzzleGenerator {
             constraints: TerminalPuzzleConstraints {},
         };
+        let mut rnd = SearchRng::seed_from_u64(0);
         for _ in 0..100 {
-            puzzle = puzzle_generator.generate(&puzzle);
+            puzzle = puzzle_generator.generate(&puzzle, &mut rnd);
             let mut count = 0;
             puzzle.pieces.iter().for_each(|piece| {
                 assert!(piece.block.is_connected());
@@ -327,6 +814,42 @@ mod tests {
         }
     }
 
+    #[test]
+    fn shape_generator() {
+        let shape = Piece::from_str(3, "xxx|xxx|xxx\nxxx|xxx|xxx\nxx.|x..|...").block;
+        let mut puzzle = Puzzle::base_in(&shape, 3, None);
+        let puzzle_generator = SwapPuzzleGenerator {
+            constraints: ShapePuzzleConstraints {
+                shape: shape.clone(),
+                next: TerminalPuzzleConstraints {},
+            },
+        };
+        let mut rnd = SearchRng::seed_from_u64(0);
+        for _ in 0..100 {
+            puzzle = puzzle_generator.generate(&puzzle, &mut rnd);
+            let mut union = Cells::empty(3);
+            for piece in puzzle.pieces.iter() {
+                union.or_inplace(&piece.block);
+            }
+            union.and_not_inplace(&shape);
+            assert_eq!(union.count(), 0);
+        }
+    }
+
+    #[test]
+    fn unsatisfiable_swap_keeps_parent() {
+        let puzzle_generator = SwapPuzzleGenerator {
+            constraints: crate::constraints::MaxPieceSizeConstraints { size: 1 },
+        };
+        let mut rnd = SearchRng::seed_from_u64(0);
+        let puzzle = Puzzle::base(3, 3, 0, None);
+        let child = puzzle_generator.generate(&puzzle, &mut rnd);
+        assert_eq!(
+            PuzzleNumFormat::from_puzzle(&child).to_block_code(),
+            PuzzleNumFormat::from_puzzle(&puzzle).to_block_code()
+        );
+    }
+
     #[test]
     fn test_drop_count() {
         use ShrinkMove::*;
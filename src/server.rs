@@ -1,7 +1,10 @@
 use crate::{
+    features::Features,
     iters::V3Iter,
+    printability::Printability,
     puzzle::{Move, Piece, Puzzle, SolveResult},
     puzzle_num_format::PuzzleNumFormat,
+    solve_cache::SolveCache,
     v3::{V3, V3I},
 };
 use axum::{
@@ -17,7 +20,7 @@ use mongodb::{
 };
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct PuzzleJson {
     #[serde(rename = "_id", skip_serializing)]
     id: Option<ObjectId>,
@@ -26,26 +29,67 @@ pub struct PuzzleJson {
     pub run: String,
     pub solution: SolutionJson,
     pub date: String,
+    /// Mirror image is a different puzzle under proper rotations
+    #[serde(default)]
+    pub chiral: bool,
+    /// Missing in puzzles written before the features
+    #[serde(default)]
+    pub features: Option<Features>,
 }
-#[derive(Serialize, Deserialize)]
-struct SolutionJson {
-    pieces: Vec<PieceJson>,
-    moves: Vec<MoveJson>,
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SolutionJson {
+    pub pieces: Vec<PieceJson>,
+    pub moves: Vec<MoveJson>,
 }
-#[derive(Serialize, Deserialize)]
-struct PieceJson {
-    blocks: Vec<CoordJson>,
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PieceJson {
+    pub blocks: Vec<CoordJson>,
+    /// Missing in puzzles exported before the analysis
+    #[serde(default)]
+    pub printability: Option<PrintabilityJson>,
+}
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PrintabilityJson {
+    pub down: CoordJson,
+    pub contact: usize,
+    pub overhangs: usize,
+    pub necks: Vec<CoordJson>,
+}
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CoordJson {
+    pub x: isize,
+    pub y: isize,
+    pub z: isize,
+}
+#[derive(Clone, Serialize, Deserialize)]
+pub struct MoveJson {
+    pub pieces: Vec<usize>,
+    pub translate: Option<CoordJson>,
 }
+
+/// Pareto front of a multi-objective run, one document per run
 #[derive(Serialize, Deserialize)]
-struct CoordJson {
-    x: isize,
-    y: isize,
-    z: isize,
+pub struct FrontJson {
+    pub run: String,
+    pub date: String,
+    pub entries: Vec<FrontEntryJson>,
 }
 #[derive(Serialize, Deserialize)]
-struct MoveJson {
-    pieces: Vec<usize>,
-    translate: Option<CoordJson>,
+pub struct FrontEntryJson {
+    pub value: String,
+    /// The value itself, to merge the front of a resumed run; missing in fronts before it
+    #[serde(default)]
+    pub objectives: Option<serde_json::Value>,
+    pub puzzle: PuzzleJson,
+}
+impl FrontJson {
+    pub fn new(run: &str, entries: Vec<FrontEntryJson>) -> Self {
+        Self {
+            run: run.to_owned(),
+            date: chrono::Local::now().to_rfc3339(),
+            entries,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize)]
@@ -83,6 +127,27 @@ pub async fn puzzles(
     (StatusCode::OK, Json(puzzles))
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct FrontQuery {
+    pub run: String,
+}
+pub async fn front(
+    State(client): State<Client>,
+    query: Query<FrontQuery>,
+) -> (StatusCode, Json<Option<FrontJson>>) {
+    let fronts = client.database("puzzle").collection::<FrontJson>("front");
+    let front = fronts
+        .find_one(mongodb::bson::doc! { "run": &query.run }, None)
+        .await
+        .unwrap();
+    let status = if front.is_some() {
+        StatusCode::OK
+    } else {
+        StatusCode::NOT_FOUND
+    };
+    (status, Json(front))
+}
+
 impl PuzzleJson {
     pub fn from_result(puzzle: &Puzzle, result: &SolveResult) -> PuzzleJson {
         let code = PuzzleNumFormat::from_puzzle(puzzle);
@@ -114,13 +179,23 @@ impl PuzzleJson {
             run: "none".to_owned(),
             solution: SolutionJson { pieces, moves },
             date: "".to_owned(),
+            chiral: code.is_chiral(),
+            features: Some(Features::of(puzzle, result)),
         }
     }
 
-    pub fn normalized_from_puzzle(puzzle: &Puzzle) -> PuzzleJson {
+    /// `mirror` also identifies the puzzle with its mirror image
+    pub fn normalized_from_puzzle(
+        puzzle: &Puzzle,
+        mirror: bool,
+        cache: Option<&SolveCache>,
+    ) -> PuzzleJson {
         let puzzle_code = PuzzleNumFormat::from_puzzle(puzzle);
-        let normalized = puzzle_code.normalize().to_puzzle();
-        let result = normalized.solve();
+        let normalized = puzzle_code.normalize_with(mirror).to_puzzle();
+        let result = match cache {
+            Some(cache) => cache.solve(&normalized),
+            None => normalized.solve(),
+        };
         PuzzleJson::from_result(&normalized, &result)
     }
 }
@@ -132,7 +207,24 @@ impl PieceJson {
                 blocks.push(CoordJson::from_v3(x))
             }
         }
-        PieceJson { blocks }
+        PieceJson {
+            blocks,
+            printability: Some(PrintabilityJson::from_printability(&piece.printability())),
+        }
+    }
+}
+impl PrintabilityJson {
+    fn from_printability(printability: &Printability) -> PrintabilityJson {
+        PrintabilityJson {
+            down: CoordJson::from_v3i(printability.down),
+            contact: printability.contact,
+            overhangs: printability.overhangs,
+            necks: printability
+                .necks
+                .iter()
+                .map(|&x| CoordJson::from_v3(x))
+                .collect(),
+        }
     }
 }
 impl CoordJson {
@@ -0,0 +1,317 @@
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+use crate::archive::SolveStats;
+use crate::puzzle::{Puzzle, SolveResult};
+use crate::puzzle_num_format::PuzzleNumFormat;
+use crate::searcher::Evaluator;
+
+/// Bump when `Puzzle::solve` changes its results; older outcomes are ignored.
+/// 2: outcomes keyed by the solver options too.
+/// 3: outcomes of the normalized puzzle rather than of the first orientation solved.
+pub const SOLVER_VERSION: u32 = 3;
+
+/// Fields of `Puzzle` besides the pieces that change the outcome of `Puzzle::solve`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct SolverOptions {
+    pub space: usize,
+    pub margin: usize,
+    pub reach_limit: Option<usize>,
+    pub multi: Option<usize>,
+}
+impl SolverOptions {
+    pub fn of(puzzle: &Puzzle) -> Self {
+        Self {
+            space: puzzle.space,
+            margin: puzzle.margin,
+            reach_limit: puzzle.reach_limit,
+            multi: puzzle.multi,
+        }
+    }
+}
+
+/// Normalized block code and the options it was solved with
+type CacheKey = (String, SolverOptions);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SolveOutcome {
+    pub version: u32,
+    pub solvable: bool,
+    pub stats: Option<SolveStats>,
+}
+impl SolveOutcome {
+    pub fn from_result(puzzle: &Puzzle, result: &SolveResult) -> Self {
+        Self {
+            version: SOLVER_VERSION,
+            solvable: result.ok,
+            stats: SolveStats::from_result(puzzle, result),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct CacheEntryJson {
+    code: String,
+    options: SolverOptions,
+    #[serde(flatten)]
+    outcome: SolveOutcome,
+}
+impl CacheEntryJson {
+    fn new((code, options): CacheKey, outcome: SolveOutcome) -> Self {
+        Self {
+            code,
+            options,
+            outcome,
+        }
+    }
+    fn key(&self) -> CacheKey {
+        (self.code.clone(), self.options)
+    }
+}
+
+/// Solve outcomes shared between threads, keyed by normalized block code and solver options,
+/// e.g. an unsolvable puzzle under a `reach_limit` may be solvable without
+pub struct SolveCache {
+    outcomes: Mutex<HashMap<CacheKey, SolveOutcome>>,
+    /// JSON lines file new outcomes are appended to
+    file: Option<Mutex<File>>,
+    /// Outcomes not yet stored by `flush_db`
+    pending: Option<Mutex<Vec<CacheEntryJson>>>,
+}
+impl Debug for SolveCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "SolveCache({} outcomes)", self.len())
+    }
+}
+impl SolveCache {
+    /// Loads `path` and appends every new outcome to it
+    pub fn open(path: &str) -> Result<Self, String> {
+        let file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)
+            .map_err(|e| format!("{}: {}", path, e))?;
+        let mut outcomes = HashMap::new();
+        for line in BufReader::new(&file).lines() {
+            let line = line.map_err(|e| format!("{}: {}", path, e))?;
+            let Ok(entry) = serde_json::from_str::<CacheEntryJson>(&line) else {
+                println!("WARN: skipping broken line in {}", path);
+                continue;
+            };
+            if entry.outcome.version == SOLVER_VERSION {
+                outcomes.insert(entry.key(), entry.outcome);
+            }
+        }
+        Ok(Self {
+            outcomes: Mutex::new(outcomes),
+            file: Some(Mutex::new(file)),
+            pending: None,
+        })
+    }
+    /// Loads the `solve_cache` collection; new outcomes are stored by `flush_db`
+    pub async fn load_db(db: &mongodb::Database) -> Result<Self, String> {
+        use futures::stream::TryStreamExt;
+        let collection = db.collection::<CacheEntryJson>("solve_cache");
+        let filter = mongodb::bson::doc! { "version": SOLVER_VERSION };
+        let entries: Vec<CacheEntryJson> = collection
+            .find(filter, None)
+            .await
+            .map_err(|e| e.to_string())?
+            .try_collect()
+            .await
+            .map_err(|e| e.to_string())?;
+        let outcomes = entries
+            .into_iter()
+            .map(|entry| (entry.key(), entry.outcome))
+            .collect();
+        Ok(Self {
+            outcomes: Mutex::new(outcomes),
+            file: None,
+            pending: Some(Mutex::new(vec![])),
+        })
+    }
+    pub async fn flush_db(&self, db: &mongodb::Database) -> Result<(), String> {
+        let Some(pending) = self.pending.as_ref() else {
+            return Ok(());
+        };
+        let entries = std::mem::take(&mut *pending.lock().unwrap());
+        if entries.is_empty() {
+            return Ok(());
+        }
+        let collection = db.collection::<CacheEntryJson>("solve_cache");
+        collection
+            .insert_many(entries, None)
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+    pub fn len(&self) -> usize {
+        self.outcomes.lock().unwrap().len()
+    }
+    fn key(puzzle: &Puzzle) -> CacheKey {
+        let code = PuzzleNumFormat::from_puzzle(puzzle)
+            .normalize()
+            .to_block_code();
+        (code, SolverOptions::of(puzzle))
+    }
+    /// The puzzle of `key` in its normalized orientation and labelling, solved for the stored
+    /// outcome: the moves the solver finds first, and so the stats, may depend on them
+    fn canonical((code, options): &CacheKey) -> Puzzle {
+        let mut puzzle = PuzzleNumFormat::from_block_code(code).to_puzzle();
+        puzzle.space = options.space;
+        puzzle.margin = options.margin;
+        puzzle.reach_limit = options.reach_limit;
+        puzzle.multi = options.multi;
+        puzzle
+    }
+    pub fn get(&self, puzzle: &Puzzle) -> Option<SolveOutcome> {
+        self.outcomes
+            .lock()
+            .unwrap()
+            .get(&Self::key(puzzle))
+            .copied()
+    }
+    fn insert(&self, key: CacheKey, outcome: SolveOutcome) {
+        if let Some(file) = self.file.as_ref() {
+            let entry = CacheEntryJson::new(key.clone(), outcome);
+            let line = serde_json::to_string(&entry).unwrap();
+            writeln!(file.lock().unwrap(), "{}", line).unwrap();
+        }
+        if let Some(pending) = self.pending.as_ref() {
+            pending
+                .lock()
+                .unwrap()
+                .push(CacheEntryJson::new(key.clone(), outcome));
+        }
+        self.outcomes.lock().unwrap().insert(key, outcome);
+    }
+    /// Always solves, since the moves are not cached, and records the outcome
+    pub fn solve(&self, puzzle: &Puzzle) -> SolveResult {
+        let result = puzzle.solve();
+        let key = Self::key(puzzle);
+        if !self.outcomes.lock().unwrap().contains_key(&key) {
+            let canonical = Self::canonical(&key);
+            let outcome = SolveOutcome::from_result(&canonical, &canonical.solve());
+            self.insert(key, outcome);
+        }
+        result
+    }
+    /// `None` if unsolvable; solves only when the outcome is unknown
+    /// or `evaluator` cannot work from the cached stats. The value is the one of the
+    /// normalized puzzle, the same for every rotation and piece labelling.
+    pub fn value<E: Evaluator>(&self, puzzle: &Puzzle, evaluator: &E) -> Option<E::Value> {
+        let key = Self::key(puzzle);
+        let cached = self.outcomes.lock().unwrap().get(&key).copied();
+        if let Some(outcome) = cached {
+            if !outcome.solvable {
+                return None;
+            }
+            if let Some(value) = outcome
+                .stats
+                .and_then(|stats| evaluator.stats_value(&stats))
+            {
+                return Some(value);
+            }
+        }
+        let canonical = Self::canonical(&key);
+        let result = canonical.solve();
+        if cached.is_none() {
+            self.insert(key, SolveOutcome::from_result(&canonical, &result));
+        }
+        result.ok.then(|| evaluator.evaluate(&canonical, &result))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cells::Cells;
+    use crate::iters::V3Iter;
+    use crate::puzzle::Piece;
+    use crate::searcher::{DupDropEvaluator, ShrinkStepEvaluator};
+    use crate::server::sample_puzzle;
+    use crate::v3::V3;
+    #[test]
+    fn test_solve_cache() {
+        let path = std::env::temp_dir().join(format!("solve_cache_{}.jsonl", std::process::id()));
+        let path = path.to_string_lossy().to_string();
+        // as stored, in its normalized orientation
+        let puzzle = SolveCache::canonical(&SolveCache::key(&sample_puzzle()));
+        let result = puzzle.solve();
+        let unsolvable =
+            PuzzleNumFormat::from_block_code("333:2:111111111111121111111111111").to_puzzle();
+        {
+            let cache = SolveCache::open(&path).unwrap();
+            let value = cache.value(&puzzle, &ShrinkStepEvaluator {});
+            assert_eq!(
+                value,
+                Some(ShrinkStepEvaluator {}.evaluate(&puzzle, &result))
+            );
+            assert!(!cache.solve(&unsolvable).ok);
+            assert_eq!(cache.len(), 2);
+        }
+        let cache = SolveCache::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(cache.len(), 2);
+        let outcome = cache.get(&puzzle).unwrap();
+        assert!(outcome.solvable);
+        assert_eq!(outcome, SolveOutcome::from_result(&puzzle, &result));
+        assert!(outcome.stats.unwrap().first > 0);
+        assert_eq!(cache.get(&unsolvable).unwrap().stats, None);
+        assert_eq!(cache.value(&unsolvable, &ShrinkStepEvaluator {}), None);
+        // no shortcut from the stats, solved again
+        let value = cache.value(&puzzle, &DupDropEvaluator {});
+        assert_eq!(value, Some(DupDropEvaluator {}.evaluate(&puzzle, &result)));
+
+        // unsolvable within a reach limit, not without it
+        let mut limited = puzzle.clone();
+        limited.reach_limit = Some(1);
+        assert!(!cache.solve(&limited).ok);
+        assert!(!cache.get(&limited).unwrap().solvable);
+        assert!(cache.get(&puzzle).unwrap().solvable);
+        let mut unlimited = puzzle.clone();
+        unlimited.reach_limit = None;
+        unlimited.multi = None;
+        assert_eq!(cache.get(&unlimited), None);
+        assert!(cache.value(&unlimited, &ShrinkStepEvaluator {}).is_some());
+    }
+    #[test]
+    fn same_outcome_for_every_orientation() {
+        let puzzle = sample_puzzle();
+        let size = puzzle.size;
+        // a quarter turn about z, with the pieces in reverse order
+        let pieces = puzzle
+            .pieces
+            .iter()
+            .rev()
+            .map(|piece| {
+                let mut block = Cells::empty(size);
+                for V3(x, y, z) in V3Iter::cube(size).filter(|&x| piece.block.getv(x)) {
+                    block.setv(V3(y, size - 1 - x, z), true);
+                }
+                Piece::from_block(&block)
+            })
+            .collect();
+        let turned = Puzzle {
+            pieces,
+            ..puzzle.clone()
+        };
+        assert_eq!(SolveCache::key(&turned), SolveCache::key(&puzzle));
+        let outcome = |puzzle: &Puzzle| {
+            let cache = SolveCache {
+                outcomes: Mutex::new(HashMap::new()),
+                file: None,
+                pending: None,
+            };
+            let value = cache.value(puzzle, &DupDropEvaluator {});
+            (value, cache.get(puzzle))
+        };
+        assert_eq!(outcome(&turned), outcome(&puzzle));
+    }
+}
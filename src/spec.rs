@@ -0,0 +1,487 @@
+use std::sync::Arc;
+
+use rand::SeedableRng;
+use serde::{Deserialize, Serialize};
+
+use crate::cells::Cells;
+use crate::constraints::*;
+use crate::genetic::GeneticSearcher;
+use crate::grow::GrowPuzzleGenerator;
+use crate::guided::GuidedPuzzleGenerator;
+use crate::island::Migration;
+use crate::launcher::{ArchiveWriter, DBWriter, Launcher, PuzzleFileWriter, RunArgs};
+use crate::mutation::{Mutation, StructurePuzzleGenerator};
+use crate::pareto::ParetoEvaluator;
+use crate::puzzle::Puzzle;
+use crate::searcher::*;
+use crate::solve_cache::SolveCache;
+use crate::strategy::{Annealing, Beam, HillClimb, Restart, Tabu};
+use crate::weighted::{Term, WeightedEvaluator};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PuzzleSpec {
+    pub size: usize,
+    pub pieces: usize,
+    #[serde(default)]
+    pub holes: usize,
+    pub reach_limit: Option<usize>,
+    /// Voxelized to `size`, pieces then stay inside the shape and `holes` is ignored
+    pub stl: Option<String>,
+    /// Grows the initial pieces from random seeds instead of slicing the cube
+    #[serde(default)]
+    pub grow: bool,
+    /// Target cells of each piece for `grow` and `regrow`
+    #[serde(default)]
+    pub sizes: Vec<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ConstraintSpec {
+    MinSize { size: usize },
+    MaxSize(MaxPieceSizeConstraints),
+    Balance(BalanceConstraints),
+    BoundingBox(MaxBoundingBoxConstraints),
+    NoEnclosed,
+    Planar,
+    FaceTouch(FaceTouchConstraints),
+    Printable(PrintableConstraints),
+    And(AndConstraints<ConstraintSpec>),
+    Or(OrConstraints<ConstraintSpec>),
+    Not(NotConstraints<ConstraintSpec>),
+}
+impl PuzzleConstraints for ConstraintSpec {
+    fn is_ok(&self, blocks: &[Cells]) -> bool {
+        match self {
+            ConstraintSpec::MinSize { size } => MinPuzzleSizeConstraints {
+                size: *size,
+                next: TerminalPuzzleConstraints {},
+            }
+            .is_ok(blocks),
+            ConstraintSpec::MaxSize(c) => c.is_ok(blocks),
+            ConstraintSpec::Balance(c) => c.is_ok(blocks),
+            ConstraintSpec::BoundingBox(c) => c.is_ok(blocks),
+            ConstraintSpec::NoEnclosed => NoEnclosedConstraints {}.is_ok(blocks),
+            ConstraintSpec::Planar => PlanarConstraints {}.is_ok(blocks),
+            ConstraintSpec::FaceTouch(c) => c.is_ok(blocks),
+            ConstraintSpec::Printable(c) => c.is_ok(blocks),
+            ConstraintSpec::And(c) => c.is_ok(blocks),
+            ConstraintSpec::Or(c) => c.is_ok(blocks),
+            ConstraintSpec::Not(c) => c.is_ok(blocks),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EvaluatorSpec {
+    ShrinkStep,
+    DupDrop,
+    /// Keeps the Pareto front of level, pieces, dead ends and printability
+    Pareto,
+    /// Weighted sum of features
+    Weighted {
+        terms: Vec<Term>,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum StrategySpec {
+    Hill(HillClimb),
+    Restart(Restart),
+    /// `schedule` as accepted by `Schedule::from_str`
+    Annealing {
+        schedule: String,
+        give_up: usize,
+    },
+    Tabu(Tabu),
+    Beam(Beam),
+    /// `GeneticSearcher` instead of `PuzzleSearcher`, a spec setting `tries` is rejected
+    Genetic {
+        population: usize,
+        generations: usize,
+        give_up: usize,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub enum WriterSpec {
+    File {
+        dir: String,
+    },
+    Archive {
+        path: String,
+    },
+    /// `run` of the spec is the run in MongoDB
+    Db {
+        #[serde(default)]
+        mirror: bool,
+    },
+}
+
+/// Structural mutations mixed into the swaps
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StructureSpec {
+    /// Rate of mutations taken from `mutations` instead of swaps
+    pub rate: f64,
+    pub mutations: Vec<Mutation>,
+    pub min_pieces: usize,
+    pub max_pieces: usize,
+}
+
+type SpecGenerator = RestartPuzzleGenerator<
+    RestartPuzzleGenerator<
+        GuidedPuzzleGenerator<Vec<Constraint>>,
+        StructurePuzzleGenerator<Vec<Constraint>>,
+    >,
+    GrowPuzzleGenerator<Vec<Constraint>>,
+>;
+
+/// Everything `main` used to hard-code for a search run, read from a JSON file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RunSpec {
+    /// Names the checkpoints, and the MongoDB run of the `db` writer
+    pub run: String,
+    pub puzzle: PuzzleSpec,
+    /// Cell swaps per mutation
+    pub swaps: usize,
+    /// Share of swaps at the cells in contact before the first removal of the parent
+    #[serde(default)]
+    pub focus: f64,
+    /// Rate of mutations growing a whole new partition instead
+    #[serde(default)]
+    pub regrow: f64,
+    /// Lets the piece and hole counts change
+    pub structure: Option<StructureSpec>,
+    #[serde(default)]
+    pub constraints: Vec<ConstraintSpec>,
+    pub evaluator: EvaluatorSpec,
+    pub strategy: StrategySpec,
+    /// Required by every strategy but `genetic`
+    pub tries: Option<usize>,
+    /// Capacity of the `VisitedCache`
+    #[serde(default)]
+    pub visited: usize,
+    /// JSON lines file of a `SolveCache`, or `db` for the MongoDB collection
+    pub cache: Option<String>,
+    pub writer: WriterSpec,
+    pub parallel: usize,
+    /// As accepted by `Migration::from_str`
+    pub migration: Option<String>,
+    pub seed: Option<u64>,
+}
+
+/// One entry of a constraint chain built at run time
+#[derive(Debug, Clone)]
+pub enum Constraint {
+    Spec(ConstraintSpec),
+    Shape(Box<ShapePuzzleConstraints<TerminalPuzzleConstraints>>),
+}
+impl PuzzleConstraints for Constraint {
+    fn is_ok(&self, blocks: &[Cells]) -> bool {
+        match self {
+            Constraint::Spec(c) => c.is_ok(blocks),
+            Constraint::Shape(c) => c.is_ok(blocks),
+        }
+    }
+}
+
+impl RunSpec {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        let spec: Self = serde_json::from_str(&text).map_err(|e| format!("{}: {}", path, e))?;
+        spec.tries().map_err(|e| format!("{}: {}", path, e))?;
+        Ok(spec)
+    }
+    /// `tries` of the `PuzzleSearcher`, `None` for `genetic` which has `generations` instead
+    fn tries(&self) -> Result<Option<usize>, String> {
+        match (&self.strategy, self.tries) {
+            (StrategySpec::Genetic { .. }, Some(_)) => {
+                Err("tries is not used by the genetic strategy".to_owned())
+            }
+            (StrategySpec::Genetic { .. }, None) => Ok(None),
+            (_, None) => Err("tries is required".to_owned()),
+            (_, tries) => Ok(tries),
+        }
+    }
+    /// `seed` grows the pieces, the seed of the run so that resumed runs start from the same
+    /// puzzle
+    fn initial(&self, seed: u64) -> Result<(Puzzle, Option<Cells>), String> {
+        let p = &self.puzzle;
+        if !p.sizes.is_empty() && p.sizes.len() != p.pieces {
+            return Err(format!("{} sizes for {} pieces", p.sizes.len(), p.pieces));
+        }
+        let (puzzle, shape) = match p.stl.as_ref() {
+            None => (Puzzle::base(p.size, p.pieces, p.holes, p.reach_limit), None),
+            Some(stl) => {
+                let bytes = std::fs::read(stl).map_err(|e| format!("{}: {}", stl, e))?;
+                let shape = crate::voxelize::voxelize(&crate::stl::read(&bytes)?, p.size);
+                let puzzle = Puzzle::base_in(&shape, p.pieces, p.reach_limit);
+                (puzzle, Some(shape))
+            }
+        };
+        let puzzle = if p.grow {
+            let mut rnd = SearchRng::seed_from_u64(seed);
+            self.grower(shape.as_ref()).generate(&puzzle, &mut rnd)
+        } else {
+            puzzle
+        };
+        // the generators only ever keep puzzles that meet them, starting from one
+        let blocks = puzzle
+            .pieces
+            .iter()
+            .map(|p| p.block.clone())
+            .collect::<Vec<_>>();
+        if !self.constraints(shape.as_ref()).is_ok(&blocks) {
+            return Err("the initial puzzle does not meet the constraints".to_owned());
+        }
+        Ok((puzzle, shape))
+    }
+    fn constraints(&self, shape: Option<&Cells>) -> Vec<Constraint> {
+        let mut constraints = self
+            .constraints
+            .iter()
+            .map(|spec| Constraint::Spec(spec.clone()))
+            .collect::<Vec<_>>();
+        if let Some(shape) = shape {
+            constraints.push(Constraint::Shape(Box::new(ShapePuzzleConstraints {
+                shape: shape.clone(),
+                next: TerminalPuzzleConstraints {},
+            })));
+        }
+        constraints
+    }
+    fn grower(&self, shape: Option<&Cells>) -> GrowPuzzleGenerator<Vec<Constraint>> {
+        GrowPuzzleGenerator {
+            sizes: self.puzzle.sizes.clone(),
+            // the holes of a shape are outside of it
+            holes: shape.is_none().then_some(self.puzzle.holes),
+            constraints: self.constraints(shape),
+        }
+    }
+    fn generator(&self, shape: Option<Cells>, cache: Option<Arc<SolveCache>>) -> SpecGenerator {
+        let structure = self.structure.clone().unwrap_or(StructureSpec {
+            rate: 0.0,
+            mutations: vec![],
+            min_pieces: self.puzzle.pieces,
+            max_pieces: self.puzzle.pieces,
+        });
+        let mut guided =
+            GuidedPuzzleGenerator::new(self.swaps, self.focus, self.constraints(shape.as_ref()));
+        if let Some(cache) = cache {
+            guided = guided.with_cache(cache);
+        }
+        RestartPuzzleGenerator {
+            generator: RestartPuzzleGenerator {
+                generator: guided,
+                restart: StructurePuzzleGenerator {
+                    mutations: structure.mutations,
+                    min_pieces: structure.min_pieces,
+                    max_pieces: structure.max_pieces,
+                    constraints: self.constraints(shape.as_ref()),
+                },
+                rate: structure.rate,
+            },
+            restart: self.grower(shape.as_ref()),
+            rate: self.regrow,
+        }
+    }
+    async fn cache(&self) -> Result<Option<Arc<SolveCache>>, String> {
+        let cache = match self.cache.as_deref() {
+            None => return Ok(None),
+            Some("db") => {
+                let client = mongodb::Client::with_uri_str(crate::get_mongo_uri())
+                    .await
+                    .map_err(|e| e.to_string())?;
+                SolveCache::load_db(&client.database("puzzle")).await?
+            }
+            Some(path) => SolveCache::open(path)?,
+        };
+        println!("INFO: {:?}", cache);
+        Ok(Some(Arc::new(cache)))
+    }
+    pub async fn launch(&self, args: &RunArgs) -> Result<(), String> {
+        match &self.evaluator {
+            EvaluatorSpec::ShrinkStep => self.launch_with(args, ShrinkStepEvaluator {}).await,
+            EvaluatorSpec::DupDrop => self.launch_with(args, DupDropEvaluator {}).await,
+            EvaluatorSpec::Pareto => self.launch_with(args, ParetoEvaluator {}).await,
+            EvaluatorSpec::Weighted { terms } => {
+                let evaluator = WeightedEvaluator {
+                    terms: terms.clone(),
+                };
+                self.launch_with(args, evaluator).await
+            }
+        }
+    }
+    async fn launch_with<E: Evaluator + 'static>(
+        &self,
+        args: &RunArgs,
+        evaluator: E,
+    ) -> Result<(), String> {
+        let tries = self.tries()?;
+        let seed = args.run_seed(&self.run, self.seed)?;
+        let (initial, shape) = self.initial(seed)?;
+        let constraints = self.constraints(shape.as_ref());
+        let cache = self.cache().await?;
+        let generator = self.generator(shape, cache.clone());
+        if let StrategySpec::Genetic {
+            population,
+            generations,
+            give_up,
+        } = self.strategy
+        {
+            let mut searcher = GeneticSearcher::new(
+                population,
+                generations,
+                initial,
+                give_up,
+                generator,
+                evaluator,
+            )
+            .with_constraints(constraints);
+            if let Some(cache) = cache.clone() {
+                searcher = searcher.with_cache(cache);
+            }
+            return self.launch_searcher(searcher, cache, seed, args).await;
+        }
+        let tries = tries.unwrap_or_default();
+        let mut searcher = PuzzleSearcher::new(tries, 1, initial, 0, generator, evaluator)
+            .with_visited(self.visited);
+        if let Some(cache) = cache.clone() {
+            searcher = searcher.with_cache(cache);
+        }
+        match &self.strategy {
+            StrategySpec::Hill(hill) => {
+                let searcher = searcher.with_strategy(hill.clone());
+                self.launch_searcher(searcher, cache, seed, args).await
+            }
+            StrategySpec::Restart(restart) => {
+                let searcher = searcher.with_strategy(restart.clone());
+                self.launch_searcher(searcher, cache, seed, args).await
+            }
+            StrategySpec::Annealing { schedule, give_up } => {
+                let searcher = searcher.with_strategy(Annealing {
+                    schedule: schedule.parse()?,
+                    give_up: *give_up,
+                });
+                self.launch_searcher(searcher, cache, seed, args).await
+            }
+            StrategySpec::Tabu(tabu) => {
+                let searcher = searcher.with_strategy(tabu.clone());
+                self.launch_searcher(searcher, cache, seed, args).await
+            }
+            StrategySpec::Beam(beam) => {
+                let searcher = searcher.with_strategy(beam.clone());
+                self.launch_searcher(searcher, cache, seed, args).await
+            }
+            StrategySpec::Genetic { .. } => unreachable!(),
+        }
+    }
+    async fn launch_searcher<S: Searcher + 'static>(
+        &self,
+        searcher: S,
+        cache: Option<Arc<SolveCache>>,
+        seed: u64,
+        args: &RunArgs,
+    ) -> Result<(), String> {
+        let spec = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        let mut launcher = Launcher::for_run(searcher, self.parallel, &self.run, Some(seed), args)?
+            .with_spec(&spec);
+        if let Some(migration) = self.migration.as_ref() {
+            launcher = launcher.with_migration(migration.parse::<Migration>()?);
+        }
+        match &self.writer {
+            WriterSpec::File { dir } => {
+                let writer = PuzzleFileWriter::new(dir.clone());
+                launcher.launch(writer).await
+            }
+            WriterSpec::Archive { path } => launcher.launch(ArchiveWriter::new(path)).await,
+            WriterSpec::Db { mirror } => {
+                let mut writer = DBWriter::new(&crate::get_mongo_uri(), &self.run, *mirror).await;
+                if let Some(cache) = cache {
+                    writer = writer.with_cache(cache);
+                }
+                launcher.launch(writer).await
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_run_spec() {
+        let spec = RunSpec::load("specs/generate_file.json").unwrap();
+        assert!(matches!(
+            spec.strategy,
+            StrategySpec::Hill(HillClimb { stack: 1, .. })
+        ));
+        let (initial, shape) = spec.initial(0).unwrap();
+        assert_eq!(initial.pieces.len(), spec.puzzle.pieces);
+        let generator = spec.generator(shape, None);
+        assert_eq!(generator.generator.generator.constraints.len(), 1);
+        let mut rnd = SearchRng::seed_from_u64(0);
+        let puzzle = generator.generate(&initial, &mut rnd);
+        assert!(puzzle.pieces.iter().all(|p| p.block.count() >= 2));
+        let mut grown = spec.clone();
+        grown.puzzle.grow = true;
+        grown.regrow = 1.0;
+        grown.seed = Some(1);
+        let (initial, shape) = grown.initial(1).unwrap();
+        let cells = |puzzle: &Puzzle| puzzle.pieces.iter().map(|p| p.block.count()).sum::<usize>();
+        assert_eq!(cells(&initial), 64 - 2);
+        let puzzle = grown.generator(shape, None).generate(&initial, &mut rnd);
+        assert_eq!(cells(&puzzle), 64 - 2);
+        assert!(puzzle.pieces.iter().all(|p| p.block.count() >= 2));
+        grown.regrow = 0.0;
+        grown.structure = Some(StructureSpec {
+            rate: 1.0,
+            mutations: vec![Mutation::Split],
+            min_pieces: 5,
+            max_pieces: 6,
+        });
+        let split = grown.generator(None, None).generate(&puzzle, &mut rnd);
+        assert_eq!(split.pieces.len(), 6);
+        grown.puzzle.sizes = vec![4];
+        assert!(grown.initial(1).is_err());
+        let mut small = spec.clone();
+        small.constraints = vec![ConstraintSpec::MaxSize(MaxPieceSizeConstraints {
+            size: 10,
+        })];
+        assert!(small.initial(0).is_err());
+        // written back by `write_config`
+        let text = serde_json::to_string(&spec).unwrap();
+        let again: RunSpec = serde_json::from_str(&text).unwrap();
+        assert_eq!(again.run, spec.run);
+        let spec = RunSpec::load("specs/generate_db.json").unwrap();
+        assert!(matches!(spec.writer, WriterSpec::Db { mirror: false }));
+        let mut genetic = spec.clone();
+        genetic.strategy = StrategySpec::Genetic {
+            population: 8,
+            generations: 10,
+            give_up: 10,
+        };
+        assert!(genetic.tries().is_err());
+        genetic.tries = None;
+        assert_eq!(genetic.tries(), Ok(None));
+        let mut hill = spec.clone();
+        hill.tries = None;
+        assert!(hill.tries().is_err());
+        assert!(serde_json::from_str::<RunSpec>(r#"{"run": "x", "typo": 1}"#).is_err());
+        let constraint: ConstraintSpec = serde_json::from_str(
+            r#"{"kind": "or", "of": [{"kind": "planar"}, {"kind": "max_size", "size": 3}]}"#,
+        )
+        .unwrap();
+        assert!(constraint.is_ok(&[Cells::empty(2)]));
+        let not = ConstraintSpec::Not(NotConstraints {
+            of: Box::new(constraint),
+        });
+        assert!(!not.is_ok(&[Cells::empty(2)]));
+    }
+}
@@ -0,0 +1,122 @@
+use crate::mesh::Mesh;
+use crate::v3::V3F;
+
+fn facet_normal([a, b, c]: &[V3F; 3]) -> V3F {
+    (*b - *a).cross(&(*c - *a)).normalize()
+}
+
+pub fn to_binary(mesh: &Mesh) -> Vec<u8> {
+    let mut bytes = vec![0u8; 80];
+    let header = b"interlocking-solver";
+    bytes[..header.len()].copy_from_slice(header);
+    bytes.extend((mesh.triangles.len() as u32).to_le_bytes());
+    for triangle in mesh.triangles.iter() {
+        for v in std::iter::once(facet_normal(triangle)).chain(triangle.iter().cloned()) {
+            for x in [v.0, v.1, v.2] {
+                bytes.extend((x as f32).to_le_bytes());
+            }
+        }
+        bytes.extend(0u16.to_le_bytes());
+    }
+    bytes
+}
+
+pub fn to_ascii(mesh: &Mesh, name: &str) -> String {
+    let mut s = format!("solid {}\n", name);
+    for triangle in mesh.triangles.iter() {
+        let V3F(nx, ny, nz) = facet_normal(triangle);
+        s.push_str(&format!(
+            "  facet normal {:e} {:e} {:e}\n    outer loop\n",
+            nx, ny, nz
+        ));
+        for V3F(x, y, z) in triangle.iter() {
+            s.push_str(&format!("      vertex {:e} {:e} {:e}\n", x, y, z));
+        }
+        s.push_str("    endloop\n  endfacet\n");
+    }
+    s.push_str(&format!("endsolid {}\n", name));
+    s
+}
+
+fn read_f32(bytes: &[u8], at: usize) -> f64 {
+    f32::from_le_bytes(bytes[at..at + 4].try_into().unwrap()) as f64
+}
+
+/// Reads binary or ASCII STL
+pub fn read(bytes: &[u8]) -> Result<Mesh, String> {
+    let binary_count = bytes
+        .get(80..84)
+        .map(|b| u32::from_le_bytes(b.try_into().unwrap()) as usize);
+    if binary_count.is_some_and(|n| bytes.len() == 84 + 50 * n) {
+        let triangles = bytes[84..]
+            .chunks(50)
+            .map(|facet| {
+                let vertex = |k: usize| {
+                    let at = 12 + k * 12;
+                    V3F(
+                        read_f32(facet, at),
+                        read_f32(facet, at + 4),
+                        read_f32(facet, at + 8),
+                    )
+                };
+                [vertex(0), vertex(1), vertex(2)]
+            })
+            .collect();
+        return Ok(Mesh { triangles });
+    }
+    let text = std::str::from_utf8(bytes).map_err(|_| "not an STL file".to_owned())?;
+    if !text.trim_start().starts_with("solid") {
+        return Err("not an STL file".to_owned());
+    }
+    let mut vertices = vec![];
+    for line in text.lines() {
+        let mut words = line.split_whitespace();
+        if words.next() != Some("vertex") {
+            continue;
+        }
+        let xyz = words
+            .map(|w| {
+                w.parse::<f64>()
+                    .map_err(|e| format!("{}: {}", line.trim(), e))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if xyz.len() != 3 {
+            return Err(format!("bad vertex: {}", line.trim()));
+        }
+        vertices.push(V3F(xyz[0], xyz[1], xyz[2]));
+    }
+    if vertices.len() % 3 != 0 {
+        return Err("facet without 3 vertices".to_owned());
+    }
+    let triangles = vertices.chunks(3).map(|t| [t[0], t[1], t[2]]).collect();
+    Ok(Mesh { triangles })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{mesh::MeshOptions, puzzle::Piece};
+    #[test]
+    fn test_stl_output() {
+        let piece = Piece::from_str(2, "x.|..\n..|..");
+        let mesh = Mesh::from_piece(&piece, &MeshOptions::default());
+        let binary = to_binary(&mesh);
+        assert_eq!(binary.len(), 84 + 50 * mesh.triangles.len());
+        let ascii = to_ascii(&mesh, "cube");
+        assert!(ascii.starts_with("solid cube\n"));
+        assert_eq!(ascii.matches("endfacet").count(), mesh.triangles.len());
+    }
+    #[test]
+    fn test_stl_read() {
+        let piece = Piece::from_str(2, "xx|..\n..|x.");
+        let mesh = Mesh::from_piece(&piece, &MeshOptions::default());
+        let binary = read(&to_binary(&mesh)).unwrap();
+        let ascii = read(to_ascii(&mesh, "piece").as_bytes()).unwrap();
+        assert_eq!(binary.triangles.len(), mesh.triangles.len());
+        assert_eq!(ascii.triangles.len(), mesh.triangles.len());
+        for (a, b) in ascii.triangles.iter().zip(mesh.triangles.iter()) {
+            assert!((a[0] - b[0]).norm() < 1e-9);
+        }
+        assert!(read(b"garbage").is_err());
+    }
+}
@@ -0,0 +1,715 @@
+use std::collections::VecDeque;
+use std::fmt::Debug;
+
+use itertools::Itertools;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::puzzle::Puzzle;
+use crate::puzzle_num_format::PuzzleNumFormat;
+use crate::searcher::EvalValue;
+
+/// Candidate with its value, `None` if unsolvable
+pub type Scored<V> = (Puzzle, Option<V>);
+
+/// Decides which puzzles `PuzzleSearcher` mutates and which candidates it keeps
+pub trait SearchStrategy<V: EvalValue>: Clone + Send + Sync + Debug {
+    type State: Debug;
+    fn init(&self, initial: &Puzzle) -> Self::State;
+    /// Puzzles to mutate at the next try
+    fn parents(&self, state: &Self::State) -> Vec<Puzzle>;
+    /// Takes one candidate per parent, in order; `false` to give up
+    fn update(
+        &self,
+        state: &mut Self::State,
+        i: usize,
+        tries: usize,
+        candidates: Vec<Scored<V>>,
+        rnd: &mut impl Rng,
+    ) -> bool;
+    /// Candidates, in order, that improve the chain of their parent; each one goes to the
+    /// writer even if the thread has a better puzzle
+    fn improvements(&self, _state: &Self::State, candidates: &[Scored<V>]) -> Vec<bool> {
+        vec![false; candidates.len()]
+    }
+    /// Takes in a puzzle found by another island
+    fn immigrate(&self, state: &mut Self::State, migrant: (Puzzle, V));
+    /// Current chains, saved by checkpoints
+    fn chains(&self, state: &Self::State) -> Vec<Chain<V>>;
+    /// Inverse of `chains`; `best` is the best value found before the checkpoint
+    fn restore(&self, initial: &Puzzle, chains: Vec<Chain<V>>, best: Option<V>) -> Self::State;
+    /// One line for the search log
+    fn describe(&self, state: &Self::State) -> String;
+}
+
+#[derive(Debug, Clone)]
+pub struct Chain<V> {
+    pub puzzle: Puzzle,
+    pub value: V,
+    /// Tries since the last improvement
+    pub count: usize,
+}
+impl<V: EvalValue> Chain<V> {
+    fn new(puzzle: &Puzzle) -> Self {
+        Self {
+            puzzle: puzzle.clone(),
+            value: V::default(),
+            count: 0,
+        }
+    }
+    /// Replaces the chain by a better puzzle and counts it as an improvement
+    fn adopt(&mut self, (puzzle, value): (Puzzle, V)) -> bool {
+        if self.value >= value {
+            return false;
+        }
+        self.puzzle = puzzle;
+        self.value = value;
+        self.count = 0;
+        true
+    }
+    /// Hill-climbing step, keeps equal candidates
+    fn climb(&mut self, (puzzle, value): Scored<V>) {
+        self.count += 1;
+        if let Some(value) = value {
+            if self.value <= value {
+                if self.value < value {
+                    self.count = 0;
+                }
+                self.puzzle = puzzle;
+                self.value = value;
+            }
+        }
+    }
+}
+
+/// `stack` independent hill-climbing chains
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HillClimb {
+    pub stack: usize,
+    pub give_up: usize,
+}
+impl<V: EvalValue> SearchStrategy<V> for HillClimb {
+    type State = Vec<Chain<V>>;
+    fn init(&self, initial: &Puzzle) -> Self::State {
+        vec![Chain::new(initial); self.stack]
+    }
+    fn parents(&self, state: &Self::State) -> Vec<Puzzle> {
+        state.iter().map(|chain| chain.puzzle.clone()).collect()
+    }
+    fn update(
+        &self,
+        state: &mut Self::State,
+        _i: usize,
+        _tries: usize,
+        candidates: Vec<Scored<V>>,
+        _rnd: &mut impl Rng,
+    ) -> bool {
+        for (chain, candidate) in state.iter_mut().zip(candidates) {
+            chain.climb(candidate);
+        }
+        state.iter().all(|chain| chain.count <= self.give_up)
+    }
+    fn improvements(&self, state: &Self::State, candidates: &[Scored<V>]) -> Vec<bool> {
+        state
+            .iter()
+            .zip(candidates)
+            .map(|(chain, (_, value))| value.is_some_and(|value| chain.value < value))
+            .collect()
+    }
+    /// Replaces the worst chain
+    fn immigrate(&self, state: &mut Self::State, migrant: (Puzzle, V)) {
+        if let Some(chain) = state.iter_mut().min_by_key(|chain| chain.value) {
+            chain.adopt(migrant);
+        }
+    }
+    fn chains(&self, state: &Self::State) -> Vec<Chain<V>> {
+        state.clone()
+    }
+    fn restore(
+        &self,
+        initial: &Puzzle,
+        mut chains: Vec<Chain<V>>,
+        _best: Option<V>,
+    ) -> Self::State {
+        chains.resize(self.stack, Chain::new(initial));
+        chains
+    }
+    fn describe(&self, state: &Self::State) -> String {
+        state
+            .iter()
+            .map(|chain| {
+                format!(
+                    "({}<{}) ({})",
+                    chain.count,
+                    self.give_up,
+                    chain.value.to_str()
+                )
+            })
+            .join(" ")
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RestartState<V> {
+    pub initial: Puzzle,
+    pub chain: Chain<V>,
+    pub restarts: usize,
+}
+/// Hill-climb that starts over from the initial puzzle when stuck
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Restart {
+    pub give_up: usize,
+    pub restarts: usize,
+}
+impl<V: EvalValue> SearchStrategy<V> for Restart {
+    type State = RestartState<V>;
+    fn init(&self, initial: &Puzzle) -> Self::State {
+        RestartState {
+            initial: initial.clone(),
+            chain: Chain::new(initial),
+            restarts: 0,
+        }
+    }
+    fn parents(&self, state: &Self::State) -> Vec<Puzzle> {
+        vec![state.chain.puzzle.clone()]
+    }
+    fn update(
+        &self,
+        state: &mut Self::State,
+        _i: usize,
+        _tries: usize,
+        candidates: Vec<Scored<V>>,
+        _rnd: &mut impl Rng,
+    ) -> bool {
+        for candidate in candidates {
+            state.chain.climb(candidate);
+        }
+        if state.chain.count > self.give_up {
+            if state.restarts >= self.restarts {
+                return false;
+            }
+            state.restarts += 1;
+            state.chain = Chain::new(&state.initial);
+        }
+        true
+    }
+    fn immigrate(&self, state: &mut Self::State, migrant: (Puzzle, V)) {
+        state.chain.adopt(migrant);
+    }
+    fn chains(&self, state: &Self::State) -> Vec<Chain<V>> {
+        vec![state.chain.clone()]
+    }
+    /// The restart count is not saved
+    fn restore(&self, initial: &Puzzle, chains: Vec<Chain<V>>, _best: Option<V>) -> Self::State {
+        let mut state = <Self as SearchStrategy<V>>::init(self, initial);
+        if let Some(chain) = chains.into_iter().next() {
+            state.chain = chain;
+        }
+        state
+    }
+    fn describe(&self, state: &Self::State) -> String {
+        format!(
+            "restart {}/{} ({}<{}) ({})",
+            state.restarts,
+            self.restarts,
+            state.chain.count,
+            self.give_up,
+            state.chain.value.to_str()
+        )
+    }
+}
+
+/// Temperature at try `i` of `tries`, in units of `EvalValue::to_scalar`
+#[derive(Debug, Clone, Copy)]
+pub enum Schedule {
+    Constant(f64),
+    Linear {
+        start: f64,
+        end: f64,
+    },
+    /// Multiplied by `rate` every try
+    Exponential {
+        start: f64,
+        rate: f64,
+    },
+}
+impl Schedule {
+    pub fn temperature(&self, i: usize, tries: usize) -> f64 {
+        match *self {
+            Schedule::Constant(t) => t,
+            Schedule::Linear { start, end } => {
+                start + (end - start) * i as f64 / tries.max(1) as f64
+            }
+            Schedule::Exponential { start, rate } => start * rate.powi(i as i32),
+        }
+    }
+}
+/// `const:T`, `linear:START:END` or `exp:START:RATE`
+impl std::str::FromStr for Schedule {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts = s.split(':').collect_vec();
+        let args = parts[1..]
+            .iter()
+            .map(|x| x.parse::<f64>().map_err(|e| format!("{}: {}", s, e)))
+            .collect::<Result<Vec<_>, _>>()?;
+        match (parts[0], &args[..]) {
+            ("const", &[t]) => Ok(Schedule::Constant(t)),
+            ("linear", &[start, end]) => Ok(Schedule::Linear { start, end }),
+            ("exp", &[start, rate]) => Ok(Schedule::Exponential { start, rate }),
+            _ => Err(format!("unknown schedule {}", s)),
+        }
+    }
+}
+
+/// Metropolis criterion for a change of `delta` (negative is worse)
+fn accept_probability(delta: f64, temperature: f64) -> f64 {
+    if delta >= 0.0 {
+        1.0
+    } else if temperature <= 0.0 {
+        0.0
+    } else {
+        (delta / temperature).exp()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AnnealingState<V> {
+    pub chain: Chain<V>,
+    pub best: V,
+    pub temperature: f64,
+}
+/// Also accepts worse candidates, with a probability following `schedule`
+#[derive(Debug, Clone)]
+pub struct Annealing {
+    pub schedule: Schedule,
+    /// Tries without a new best
+    pub give_up: usize,
+}
+impl<V: EvalValue> SearchStrategy<V> for Annealing {
+    type State = AnnealingState<V>;
+    fn init(&self, initial: &Puzzle) -> Self::State {
+        AnnealingState {
+            chain: Chain::new(initial),
+            best: V::default(),
+            temperature: self.schedule.temperature(0, 1),
+        }
+    }
+    fn parents(&self, state: &Self::State) -> Vec<Puzzle> {
+        vec![state.chain.puzzle.clone()]
+    }
+    fn update(
+        &self,
+        state: &mut Self::State,
+        i: usize,
+        tries: usize,
+        candidates: Vec<Scored<V>>,
+        rnd: &mut impl Rng,
+    ) -> bool {
+        state.temperature = self.schedule.temperature(i, tries);
+        for (puzzle, value) in candidates {
+            state.chain.count += 1;
+            let Some(value) = value else {
+                continue;
+            };
+            let delta = value.to_scalar() - state.chain.value.to_scalar();
+            if rnd.gen::<f64>() >= accept_probability(delta, state.temperature) {
+                continue;
+            }
+            if state.best < value {
+                state.best = value;
+                state.chain.count = 0;
+            }
+            state.chain.puzzle = puzzle;
+            state.chain.value = value;
+        }
+        state.chain.count <= self.give_up
+    }
+    fn immigrate(&self, state: &mut Self::State, migrant: (Puzzle, V)) {
+        if state.chain.adopt(migrant) {
+            state.best = state.best.max(state.chain.value);
+        }
+    }
+    fn chains(&self, state: &Self::State) -> Vec<Chain<V>> {
+        vec![state.chain.clone()]
+    }
+    fn restore(&self, initial: &Puzzle, chains: Vec<Chain<V>>, best: Option<V>) -> Self::State {
+        let mut state = <Self as SearchStrategy<V>>::init(self, initial);
+        if let Some(chain) = chains.into_iter().next() {
+            state.best = best.unwrap_or_default().max(chain.value);
+            state.chain = chain;
+        }
+        state
+    }
+    fn describe(&self, state: &Self::State) -> String {
+        format!(
+            "t={:.3} ({}<{}) ({}) best ({})",
+            state.temperature,
+            state.chain.count,
+            self.give_up,
+            state.chain.value.to_str(),
+            state.best.to_str()
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TabuState<V> {
+    pub chain: Chain<V>,
+    pub best: V,
+    /// Block codes of the latest moves
+    pub recent: VecDeque<String>,
+}
+/// Moves to the best of `neighbors` candidates not visited in the last `tenure` moves,
+/// even when it is worse
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Tabu {
+    pub neighbors: usize,
+    pub tenure: usize,
+    pub give_up: usize,
+}
+impl<V: EvalValue> SearchStrategy<V> for Tabu {
+    type State = TabuState<V>;
+    fn init(&self, initial: &Puzzle) -> Self::State {
+        TabuState {
+            chain: Chain::new(initial),
+            best: V::default(),
+            recent: VecDeque::new(),
+        }
+    }
+    fn parents(&self, state: &Self::State) -> Vec<Puzzle> {
+        vec![state.chain.puzzle.clone(); self.neighbors]
+    }
+    fn update(
+        &self,
+        state: &mut Self::State,
+        _i: usize,
+        _tries: usize,
+        candidates: Vec<Scored<V>>,
+        _rnd: &mut impl Rng,
+    ) -> bool {
+        state.chain.count += 1;
+        let allowed = candidates
+            .into_iter()
+            .filter_map(|(puzzle, value)| {
+                let code = PuzzleNumFormat::from_puzzle(&puzzle).to_block_code();
+                value
+                    .filter(|_| !state.recent.contains(&code))
+                    .map(|value| (puzzle, value, code))
+            })
+            .max_by_key(|(_, value, _)| *value);
+        if let Some((puzzle, value, code)) = allowed {
+            if state.best < value {
+                state.best = value;
+                state.chain.count = 0;
+            }
+            state.chain.puzzle = puzzle;
+            state.chain.value = value;
+            state.recent.push_back(code);
+            while state.recent.len() > self.tenure {
+                state.recent.pop_front();
+            }
+        }
+        state.chain.count <= self.give_up
+    }
+    fn immigrate(&self, state: &mut Self::State, migrant: (Puzzle, V)) {
+        if state.chain.adopt(migrant) {
+            state.best = state.best.max(state.chain.value);
+        }
+    }
+    fn chains(&self, state: &Self::State) -> Vec<Chain<V>> {
+        vec![state.chain.clone()]
+    }
+    fn restore(&self, initial: &Puzzle, chains: Vec<Chain<V>>, best: Option<V>) -> Self::State {
+        let mut state = <Self as SearchStrategy<V>>::init(self, initial);
+        if let Some(chain) = chains.into_iter().next() {
+            state.best = best.unwrap_or_default().max(chain.value);
+            state.chain = chain;
+        }
+        state
+    }
+    fn describe(&self, state: &Self::State) -> String {
+        format!(
+            "tabu {} ({}<{}) ({}) best ({})",
+            state.recent.len(),
+            state.chain.count,
+            self.give_up,
+            state.chain.value.to_str(),
+            state.best.to_str()
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BeamState<V> {
+    /// Best first
+    pub beam: Vec<(Puzzle, V)>,
+    pub count: usize,
+}
+/// Keeps the `width` best distinct puzzles, mutating each `branching` times per try
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Beam {
+    pub width: usize,
+    pub branching: usize,
+    pub give_up: usize,
+}
+impl Beam {
+    fn merge<V: EvalValue>(
+        &self,
+        state: &mut BeamState<V>,
+        puzzles: impl IntoIterator<Item = (Puzzle, V)>,
+    ) {
+        let mut pool = std::mem::take(&mut state.beam);
+        pool.extend(puzzles);
+        pool.sort_by_key(|(_, value)| std::cmp::Reverse(*value));
+        state.beam = pool
+            .into_iter()
+            .unique_by(|(puzzle, _)| PuzzleNumFormat::from_puzzle(puzzle).to_block_code())
+            .take(self.width)
+            .collect();
+    }
+}
+impl<V: EvalValue> SearchStrategy<V> for Beam {
+    type State = BeamState<V>;
+    fn init(&self, initial: &Puzzle) -> Self::State {
+        BeamState {
+            beam: vec![(initial.clone(), V::default())],
+            count: 0,
+        }
+    }
+    fn parents(&self, state: &Self::State) -> Vec<Puzzle> {
+        state
+            .beam
+            .iter()
+            .flat_map(|(puzzle, _)| vec![puzzle.clone(); self.branching])
+            .collect()
+    }
+    fn update(
+        &self,
+        state: &mut Self::State,
+        _i: usize,
+        _tries: usize,
+        candidates: Vec<Scored<V>>,
+        _rnd: &mut impl Rng,
+    ) -> bool {
+        let top = state.beam[0].1;
+        self.merge(
+            state,
+            candidates
+                .into_iter()
+                .filter_map(|(puzzle, value)| value.map(|value| (puzzle, value))),
+        );
+        state.count = if top < state.beam[0].1 {
+            0
+        } else {
+            state.count + 1
+        };
+        state.count <= self.give_up
+    }
+    fn immigrate(&self, state: &mut Self::State, migrant: (Puzzle, V)) {
+        self.merge(state, [migrant]);
+    }
+    /// Every chain carries the shared give-up counter
+    fn chains(&self, state: &Self::State) -> Vec<Chain<V>> {
+        state
+            .beam
+            .iter()
+            .map(|(puzzle, value)| Chain {
+                puzzle: puzzle.clone(),
+                value: *value,
+                count: state.count,
+            })
+            .collect()
+    }
+    fn restore(&self, initial: &Puzzle, chains: Vec<Chain<V>>, _best: Option<V>) -> Self::State {
+        if chains.is_empty() {
+            return <Self as SearchStrategy<V>>::init(self, initial);
+        }
+        BeamState {
+            count: chains[0].count,
+            beam: chains
+                .into_iter()
+                .map(|chain| (chain.puzzle, chain.value))
+                .collect(),
+        }
+    }
+    fn describe(&self, state: &Self::State) -> String {
+        format!(
+            "beam {} ({}<{}) ({})",
+            state.beam.len(),
+            state.count,
+            self.give_up,
+            state.beam[0].1.to_str()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::searcher::ShrinkStepValue;
+
+    #[test]
+    fn annealing_schedule() {
+        let linear = Schedule::Linear {
+            start: 2.0,
+            end: 0.0,
+        };
+        assert_eq!(linear.temperature(0, 10), 2.0);
+        assert_eq!(linear.temperature(5, 10), 1.0);
+        let exponential = Schedule::Exponential {
+            start: 1.0,
+            rate: 0.5,
+        };
+        assert_eq!(exponential.temperature(2, 10), 0.25);
+        assert!(matches!(
+            "linear:2:0".parse::<Schedule>(),
+            Ok(Schedule::Linear { .. })
+        ));
+        assert!(matches!("const:1".parse(), Ok(Schedule::Constant(_))));
+        assert!("exp:1".parse::<Schedule>().is_err());
+        assert_eq!(accept_probability(1.0, 0.0), 1.0);
+        assert_eq!(accept_probability(-1.0, 0.0), 0.0);
+        assert!((accept_probability(-1.0, 1.0) - (-1f64).exp()).abs() < 1e-12);
+        // scalar keeps the lexicographic order
+        let values = [
+            ShrinkStepValue(1, 9, 9),
+            ShrinkStepValue(2, 0, 0),
+            ShrinkStepValue(2, 0, 1),
+            ShrinkStepValue(2, 1, 0),
+        ];
+        for pair in values.windows(2) {
+            assert!(pair[0] < pair[1]);
+            assert!(pair[0].to_scalar() < pair[1].to_scalar());
+        }
+    }
+
+    #[test]
+    fn restart_when_stuck() {
+        let mut rnd = rand::thread_rng();
+        let restart = Restart {
+            give_up: 1,
+            restarts: 1,
+        };
+        let a = Puzzle::base(2, 2, 0, None);
+        let b = PuzzleNumFormat::from_block_code("222:2:11111122").to_puzzle();
+        let mut state: RestartState<ShrinkStepValue> = restart.init(&a);
+        let value = Some(ShrinkStepValue(1, 0, 0));
+        assert!(restart.update(&mut state, 0, 1, vec![(b.clone(), value)], &mut rnd));
+        assert!(restart.update(&mut state, 1, 1, vec![(b.clone(), None)], &mut rnd));
+        assert!(restart.update(&mut state, 2, 1, vec![(b.clone(), None)], &mut rnd));
+        // back to the initial puzzle
+        assert_eq!(state.restarts, 1);
+        assert_eq!(state.chain.value, ShrinkStepValue::default());
+        assert_eq!(restart.parents(&state)[0].pieces.len(), a.pieces.len());
+        restart.update(&mut state, 3, 1, vec![(b.clone(), None)], &mut rnd);
+        assert!(!restart.update(&mut state, 4, 1, vec![(b, None)], &mut rnd));
+    }
+
+    #[test]
+    fn tabu_skips_recent() {
+        let mut rnd = rand::thread_rng();
+        let tabu = Tabu {
+            neighbors: 2,
+            tenure: 1,
+            give_up: 10,
+        };
+        let a = Puzzle::base(2, 2, 0, None);
+        let b = PuzzleNumFormat::from_block_code("222:2:11111122").to_puzzle();
+        let mut state: TabuState<ShrinkStepValue> = tabu.init(&a);
+        let good = ShrinkStepValue(2, 0, 0);
+        let bad = ShrinkStepValue(1, 0, 0);
+        let candidates = vec![(a, Some(good)), (b, Some(bad))];
+        tabu.update(&mut state, 0, 1, candidates.clone(), &mut rnd);
+        assert_eq!(state.chain.value, good);
+        // the better candidate was just visited, so move to the worse one
+        tabu.update(&mut state, 1, 1, candidates, &mut rnd);
+        assert_eq!(state.chain.value, bad);
+        assert_eq!(state.best, good);
+    }
+
+    #[test]
+    fn beam_keeps_best_distinct() {
+        let mut rnd = rand::thread_rng();
+        let beam = Beam {
+            width: 2,
+            branching: 2,
+            give_up: 10,
+        };
+        let a = Puzzle::base(2, 2, 0, None);
+        let b = PuzzleNumFormat::from_block_code("222:2:11111122").to_puzzle();
+        let c = PuzzleNumFormat::from_block_code("222:2:11111222").to_puzzle();
+        let mut state: BeamState<ShrinkStepValue> = beam.init(&a);
+        assert_eq!(beam.parents(&state).len(), 2);
+        let candidates = vec![
+            (b.clone(), Some(ShrinkStepValue(3, 0, 0))),
+            (b, Some(ShrinkStepValue(3, 0, 0))),
+            (c, Some(ShrinkStepValue(2, 0, 0))),
+        ];
+        beam.update(&mut state, 0, 1, candidates, &mut rnd);
+        let values = state.beam.iter().map(|(_, v)| *v).collect_vec();
+        assert_eq!(
+            values,
+            vec![ShrinkStepValue(3, 0, 0), ShrinkStepValue(2, 0, 0)]
+        );
+        assert_eq!(state.count, 0);
+        assert_eq!(beam.parents(&state).len(), 4);
+    }
+
+    #[test]
+    fn immigrate_better_only() {
+        let hill = HillClimb {
+            stack: 2,
+            give_up: 10,
+        };
+        let a = Puzzle::base(2, 2, 0, None);
+        let b = PuzzleNumFormat::from_block_code("222:2:11111122").to_puzzle();
+        let mut state: Vec<Chain<ShrinkStepValue>> = hill.init(&a);
+        state[0].value = ShrinkStepValue(2, 0, 0);
+        state[1].value = ShrinkStepValue(3, 0, 0);
+        state[1].count = 5;
+        hill.immigrate(&mut state, (b.clone(), ShrinkStepValue(1, 0, 0)));
+        assert_eq!(state[0].value, ShrinkStepValue(2, 0, 0));
+        // the worst chain is replaced
+        hill.immigrate(&mut state, (b.clone(), ShrinkStepValue(4, 0, 0)));
+        assert_eq!(state[0].value, ShrinkStepValue(4, 0, 0));
+        assert_eq!(state[1].count, 5);
+
+        let annealing = Annealing {
+            schedule: Schedule::Constant(1.0),
+            give_up: 10,
+        };
+        let mut state: AnnealingState<ShrinkStepValue> = annealing.init(&a);
+        annealing.immigrate(&mut state, (b, ShrinkStepValue(2, 0, 0)));
+        assert_eq!(state.best, ShrinkStepValue(2, 0, 0));
+        assert_eq!(state.chain.count, 0);
+    }
+
+    #[test]
+    fn restore_keeps_best() {
+        let a = Puzzle::base(2, 2, 0, None);
+        let mut chain = Chain::new(&a);
+        chain.value = ShrinkStepValue(2, 0, 0);
+        chain.count = 3;
+        let best = Some(ShrinkStepValue(5, 0, 0));
+        let annealing = Annealing {
+            schedule: Schedule::Constant(1.0),
+            give_up: 10,
+        };
+        let state = annealing.restore(&a, vec![chain.clone()], best);
+        assert_eq!(
+            (state.best, state.chain.count),
+            (ShrinkStepValue(5, 0, 0), 3)
+        );
+        let tabu = Tabu {
+            neighbors: 2,
+            tenure: 2,
+            give_up: 10,
+        };
+        let state = tabu.restore(&a, vec![chain.clone()], best);
+        assert_eq!(state.best, ShrinkStepValue(5, 0, 0));
+        // a worse saved best never lowers the chain
+        let state = tabu.restore(&a, vec![chain], Some(ShrinkStepValue(1, 0, 0)));
+        assert_eq!(state.best, ShrinkStepValue(2, 0, 0));
+    }
+}
@@ -0,0 +1,280 @@
+use std::collections::HashSet;
+
+use crate::gltf::COLORS;
+use crate::puzzle::{Piece, Puzzle, ShrinkMove, SolveResult};
+use crate::v3::{V3F, V3I};
+
+/// Edge length of a cube in the drawings
+const UNIT: f64 = 20.0;
+
+fn color(i: usize, light: f64) -> String {
+    let c = COLORS[i % COLORS.len()];
+    let channel = |x: u32| (x as f64 * light).min(255.0) as u32;
+    format!(
+        "#{:02x}{:02x}{:02x}",
+        channel(c >> 16 & 0xff),
+        channel(c >> 8 & 0xff),
+        channel(c & 0xff)
+    )
+}
+
+/// Isometric view from (+x, +y, +z), z up
+fn project(p: V3F) -> (f64, f64) {
+    let c = 30f64.to_radians().cos();
+    ((p.0 - p.1) * c * UNIT, ((p.0 + p.1) * 0.5 - p.2) * UNIT)
+}
+
+#[derive(Default)]
+struct Drawing {
+    body: String,
+    min: (f64, f64),
+    max: (f64, f64),
+}
+impl Drawing {
+    fn extend(&mut self, (x, y): (f64, f64)) {
+        if self.body.is_empty() {
+            (self.min, self.max) = ((x, y), (x, y));
+        }
+        self.min = (self.min.0.min(x), self.min.1.min(y));
+        self.max = (self.max.0.max(x), self.max.1.max(y));
+    }
+    fn polygon(&mut self, points: &[(f64, f64)], fill: &str, opacity: f64) {
+        for &p in points {
+            self.extend(p);
+        }
+        let points = points
+            .iter()
+            .map(|(x, y)| format!("{:.1},{:.1}", x, y))
+            .collect::<Vec<_>>()
+            .join(" ");
+        self.body.push_str(&format!(
+            "<polygon points=\"{}\" fill=\"{}\" fill-opacity=\"{}\" stroke=\"#333\" stroke-width=\"0.5\"/>\n",
+            points, fill, opacity
+        ));
+    }
+    fn arrow(&mut self, from: (f64, f64), to: (f64, f64)) {
+        self.extend(from);
+        self.extend(to);
+        self.body.push_str(&format!(
+            "<line x1=\"{:.1}\" y1=\"{:.1}\" x2=\"{:.1}\" y2=\"{:.1}\" stroke=\"#d22\" stroke-width=\"2.5\" marker-end=\"url(#arrow)\"/>\n",
+            from.0, from.1, to.0, to.1
+        ));
+    }
+    fn text(&mut self, (x, y): (f64, f64), s: &str) {
+        self.extend((x, y));
+        self.body.push_str(&format!(
+            "<text x=\"{:.1}\" y=\"{:.1}\" font-size=\"10\" font-family=\"sans-serif\">{}</text>\n",
+            x, y, s
+        ));
+    }
+    fn to_svg(&self) -> String {
+        let margin = UNIT;
+        let (x, y) = (self.min.0 - margin, self.min.1 - margin);
+        let (w, h) = (
+            self.max.0 - self.min.0 + margin * 2.0,
+            self.max.1 - self.min.1 + margin * 2.0,
+        );
+        format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"{:.1} {:.1} {:.1} {:.1}\" width=\"{:.0}\" height=\"{:.0}\">\n\
+<defs><marker id=\"arrow\" markerWidth=\"6\" markerHeight=\"6\" refX=\"5\" refY=\"3\" orient=\"auto\">\
+<path d=\"M0,0 L6,3 L0,6 z\" fill=\"#d22\"/></marker></defs>\n{}</svg>\n",
+            x, y, w, h, w, h, self.body
+        )
+    }
+}
+
+/// Draws unit cubes given as (position, piece index, opacity), hiding shared faces
+fn draw_voxels(drawing: &mut Drawing, voxels: &[(V3I, usize, f64)]) {
+    let filled = voxels.iter().map(|v| v.0).collect::<HashSet<_>>();
+    let mut voxels = voxels.to_vec();
+    voxels.sort_by_key(|(V3I(x, y, z), _, _)| (x + y + z, *z));
+    for (p, i, opacity) in voxels {
+        let corner = |dx: isize, dy: isize, dz: isize| project(V3F::from(p + V3I(dx, dy, dz)));
+        let faces = [
+            (
+                V3I(0, 0, 1),
+                [(0, 0, 1), (1, 0, 1), (1, 1, 1), (0, 1, 1)],
+                1.15,
+            ),
+            (
+                V3I(1, 0, 0),
+                [(1, 0, 0), (1, 1, 0), (1, 1, 1), (1, 0, 1)],
+                0.85,
+            ),
+            (
+                V3I(0, 1, 0),
+                [(0, 1, 0), (1, 1, 0), (1, 1, 1), (0, 1, 1)],
+                0.65,
+            ),
+        ];
+        for (d, points, light) in faces {
+            if filled.contains(&(p + d)) {
+                continue;
+            }
+            let points = points.map(|(x, y, z)| corner(x, y, z));
+            drawing.polygon(&points, &color(i, light), opacity);
+        }
+    }
+}
+
+fn piece_voxels(piece: &Piece, offset: V3I, i: usize, opacity: f64) -> Vec<(V3I, usize, f64)> {
+    piece
+        .sparse
+        .iter()
+        .map(|&v| (V3I::from(v) + offset, i, opacity))
+        .collect()
+}
+
+/// Isometric drawing of the `i`-th piece
+pub fn piece_isometric(piece: &Piece, i: usize) -> String {
+    let mut drawing = Drawing::default();
+    draw_voxels(&mut drawing, &piece_voxels(piece, V3I(0, 0, 0), i, 1.0));
+    drawing.to_svg()
+}
+
+/// One grid per string of `Cells::to_str`: x to the right, z downwards
+pub fn piece_layers(piece: &Piece, i: usize) -> String {
+    let mut drawing = Drawing::default();
+    let size = piece.size;
+    let cell = UNIT * 0.75;
+    for y in 0..size {
+        let left = (y * (size + 1)) as f64 * cell;
+        drawing.text((left, -4.0), &format!("layer {}", y));
+        for z in 0..size {
+            for x in 0..size {
+                let (cx, cy) = (left + x as f64 * cell, z as f64 * cell);
+                let points = [
+                    (cx, cy),
+                    (cx + cell, cy),
+                    (cx + cell, cy + cell),
+                    (cx, cy + cell),
+                ];
+                if piece.block.get(x, y, z) {
+                    drawing.polygon(&points, &color(i, 1.0), 1.0);
+                } else {
+                    drawing.polygon(&points, "#fff", 1.0);
+                }
+            }
+        }
+    }
+    drawing.to_svg()
+}
+
+fn center(puzzle: &Puzzle, pieces: &[usize], positions: &[Option<V3I>]) -> V3F {
+    let mut sum = V3F::default();
+    let mut count = 0;
+    for &i in pieces {
+        for &v in puzzle.pieces[i].sparse.iter() {
+            sum = sum + V3F::from(V3I::from(v) + positions[i].unwrap()) + V3F(0.5, 0.5, 0.5);
+            count += 1;
+        }
+    }
+    sum * (1.0 / count as f64)
+}
+
+/// Remaining pieces before the move, with an arrow for its translation
+fn step_svg(
+    puzzle: &Puzzle,
+    positions: &[Option<V3I>],
+    moving: &[usize],
+    translate: V3I,
+) -> String {
+    let mut drawing = Drawing::default();
+    let voxels = positions
+        .iter()
+        .enumerate()
+        .filter_map(|(i, p)| {
+            let opacity = if moving.contains(&i) { 1.0 } else { 0.35 };
+            p.map(|p| piece_voxels(&puzzle.pieces[i], p, i, opacity))
+        })
+        .flatten()
+        .collect::<Vec<_>>();
+    draw_voxels(&mut drawing, &voxels);
+    let from = center(puzzle, moving, positions);
+    drawing.arrow(project(from), project(from + V3F::from(translate)));
+    drawing.to_svg()
+}
+
+fn describe(v: &V3I) -> String {
+    format!("({}, {}, {})", v.0, v.1, v.2)
+}
+
+/// Printable HTML booklet: the pieces, then the disassembly step by step
+pub fn instruction_sheet(puzzle: &Puzzle, result: &SolveResult, title: &str) -> String {
+    let shrink_moves = result.shrink_move(&result.moves(puzzle));
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n\
+<style>body {{ font-family: sans-serif; }} .step, .piece {{ display: inline-block; margin: 8px; vertical-align: top; page-break-inside: avoid; }}</style>\n\
+</head>\n<body>\n<h1>{}</h1>\n<h2>Pieces</h2>\n",
+        title, title
+    );
+    for (i, piece) in puzzle.pieces.iter().enumerate() {
+        html.push_str(&format!(
+            "<div class=\"piece\">\n<h3>#{}</h3>\n{}{}</div>\n",
+            i,
+            piece_isometric(piece, i),
+            piece_layers(piece, i)
+        ));
+    }
+    html.push_str("<h2>Disassembly</h2>\n");
+    let mut positions = vec![Some(V3I(0, 0, 0)); puzzle.pieces.len()];
+    for (k, mov) in shrink_moves.iter().enumerate() {
+        let (caption, svg) = match mov {
+            ShrinkMove::Shift(pieces, vs) => {
+                let total = vs.iter().fold(V3I(0, 0, 0), |s, v| s + *v);
+                let svg = step_svg(puzzle, &positions, pieces, total);
+                for &i in pieces {
+                    positions[i] = positions[i].map(|p| p + total);
+                }
+                let names = pieces.iter().map(|i| format!("#{}", i)).collect::<Vec<_>>();
+                let steps = vs.iter().map(describe).collect::<Vec<_>>();
+                let caption = format!("Move {} by {}", names.join(", "), steps.join(" then "));
+                (caption, svg)
+            }
+            ShrinkMove::Remove(i, d) => {
+                let svg = step_svg(puzzle, &positions, &[*i], *d * 2usize);
+                positions[*i] = None;
+                let caption = if *d == V3I(0, 0, 0) {
+                    format!("Remove #{}", i)
+                } else {
+                    format!("Remove #{} towards {}", i, describe(d))
+                };
+                (caption, svg)
+            }
+        };
+        html.push_str(&format!(
+            "<div class=\"step\">\n<h3>Step {}</h3>\n<p>{}</p>\n{}</div>\n",
+            k + 1,
+            caption,
+            svg
+        ));
+    }
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::sample_puzzle;
+    #[test]
+    fn test_piece_isometric() {
+        let piece = Piece::from_str(2, "xx|..\n..|..");
+        let svg = piece_isometric(&piece, 0);
+        assert!(svg.starts_with("<svg"));
+        // shared face between the two cubes is hidden
+        assert_eq!(svg.matches("<polygon").count(), 5);
+        let svg = piece_layers(&piece, 0);
+        assert_eq!(svg.matches("<polygon").count(), 8);
+    }
+    #[test]
+    fn test_instruction_sheet() {
+        let puzzle = sample_puzzle();
+        let result = puzzle.solve();
+        let shrink_moves = result.shrink_move(&result.moves(&puzzle));
+        let html = instruction_sheet(&puzzle, &result, "sample");
+        assert_eq!(html.matches("class=\"step\"").count(), shrink_moves.len());
+        assert_eq!(html.matches("class=\"piece\"").count(), puzzle.pieces.len());
+        assert_eq!(html.matches("Remove #").count(), puzzle.pieces.len());
+    }
+}
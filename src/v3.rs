@@ -4,6 +4,8 @@ use std::ops::{Add, Mul, Sub};
 pub struct V3(pub usize, pub usize, pub usize);
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct V3I(pub isize, pub isize, pub isize);
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct V3F(pub f64, pub f64, pub f64);
 
 impl V3 {
     pub fn cube(x: usize) -> Self {
@@ -76,6 +78,72 @@ impl From<V3> for V3I {
     }
 }
 
+impl Add for V3F {
+    type Output = V3F;
+    fn add(self, other: V3F) -> V3F {
+        V3F(self.0 + other.0, self.1 + other.1, self.2 + other.2)
+    }
+}
+impl Sub for V3F {
+    type Output = V3F;
+    fn sub(self, other: V3F) -> V3F {
+        V3F(self.0 - other.0, self.1 - other.1, self.2 - other.2)
+    }
+}
+impl Mul<f64> for V3F {
+    type Output = V3F;
+    fn mul(self, other: f64) -> V3F {
+        V3F(self.0 * other, self.1 * other, self.2 * other)
+    }
+}
+impl V3F {
+    pub fn dot(&self, other: &V3F) -> f64 {
+        self.0 * other.0 + self.1 * other.1 + self.2 * other.2
+    }
+    pub fn cross(&self, other: &V3F) -> V3F {
+        V3F(
+            self.1 * other.2 - self.2 * other.1,
+            self.2 * other.0 - self.0 * other.2,
+            self.0 * other.1 - self.1 * other.0,
+        )
+    }
+    pub fn norm(&self) -> f64 {
+        self.dot(self).sqrt()
+    }
+    pub fn normalize(&self) -> V3F {
+        let norm = self.norm();
+        if norm == 0.0 {
+            *self
+        } else {
+            *self * (1.0 / norm)
+        }
+    }
+    pub fn min(&self, other: &V3F) -> V3F {
+        V3F(
+            self.0.min(other.0),
+            self.1.min(other.1),
+            self.2.min(other.2),
+        )
+    }
+    pub fn max(&self, other: &V3F) -> V3F {
+        V3F(
+            self.0.max(other.0),
+            self.1.max(other.1),
+            self.2.max(other.2),
+        )
+    }
+}
+impl From<V3> for V3F {
+    fn from(v: V3) -> V3F {
+        V3F(v.0 as f64, v.1 as f64, v.2 as f64)
+    }
+}
+impl From<V3I> for V3F {
+    fn from(v: V3I) -> V3F {
+        V3F(v.0 as f64, v.1 as f64, v.2 as f64)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
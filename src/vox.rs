@@ -0,0 +1,193 @@
+use crate::cells::Cells;
+use crate::gltf::COLORS;
+use crate::puzzle::Puzzle;
+use crate::puzzle_num_format::PuzzleNumFormat;
+use crate::v3::V3;
+
+const VERSION: i32 = 150;
+/// Largest model of MagicaVoxel
+const MAX_SIZE: usize = 256;
+/// Palette indices of the pieces, 0 is empty
+const MAX_PIECES: usize = 255;
+
+fn chunk(id: &[u8; 4], content: &[u8], children: &[u8]) -> Vec<u8> {
+    let mut bytes = id.to_vec();
+    bytes.extend((content.len() as i32).to_le_bytes());
+    bytes.extend((children.len() as i32).to_le_bytes());
+    bytes.extend(content);
+    bytes.extend(children);
+    bytes
+}
+
+/// MagicaVoxel model, palette index `i + 1` for the `i`-th piece
+pub fn to_vox(puzzle: &Puzzle) -> Result<Vec<u8>, String> {
+    if puzzle.size > MAX_SIZE {
+        return Err(format!("size {} above {}", puzzle.size, MAX_SIZE));
+    }
+    if puzzle.pieces.len() > MAX_PIECES {
+        return Err(format!(
+            "{} pieces above {}",
+            puzzle.pieces.len(),
+            MAX_PIECES
+        ));
+    }
+    let size = puzzle.size as i32;
+    let mut dims = vec![];
+    for d in [size, size, size] {
+        dims.extend(d.to_le_bytes());
+    }
+    let mut voxels = vec![];
+    let mut count = 0i32;
+    for (i, piece) in puzzle.pieces.iter().enumerate() {
+        for v in piece.sparse.iter() {
+            voxels.extend([v.0 as u8, v.1 as u8, v.2 as u8, i as u8 + 1]);
+            count += 1;
+        }
+    }
+    let mut xyzi = count.to_le_bytes().to_vec();
+    xyzi.extend(voxels);
+    let mut rgba = vec![];
+    for i in 0..256 {
+        let c = if i < puzzle.pieces.len() {
+            COLORS[i % COLORS.len()]
+        } else {
+            0x808080
+        };
+        rgba.extend([(c >> 16) as u8, (c >> 8) as u8, c as u8, 255]);
+    }
+    let mut children = chunk(b"SIZE", &dims, &[]);
+    children.extend(chunk(b"XYZI", &xyzi, &[]));
+    children.extend(chunk(b"RGBA", &rgba, &[]));
+    let mut bytes = b"VOX ".to_vec();
+    bytes.extend(VERSION.to_le_bytes());
+    bytes.extend(chunk(b"MAIN", &[], &children));
+    Ok(bytes)
+}
+
+fn read_i32(bytes: &[u8], at: usize) -> Result<i32, String> {
+    at.checked_add(4)
+        .and_then(|end| bytes.get(at..end))
+        .map(|b| i32::from_le_bytes(b.try_into().unwrap()))
+        .ok_or_else(|| format!("unexpected end of file at {}", at))
+}
+/// A length or count, which must not be negative
+fn read_len(bytes: &[u8], at: usize) -> Result<usize, String> {
+    let n = read_i32(bytes, at)?;
+    usize::try_from(n).map_err(|_| format!("negative length {} at {}", n, at))
+}
+fn offset(a: usize, b: usize) -> Result<usize, String> {
+    a.checked_add(b)
+        .ok_or_else(|| "chunk beyond the end of file".to_owned())
+}
+
+/// Reads the first model; each used palette index becomes a piece, in ascending order
+pub fn from_vox(bytes: &[u8]) -> Result<PuzzleNumFormat, String> {
+    if bytes.get(0..4) != Some(b"VOX ") {
+        return Err("not a vox file".to_owned());
+    }
+    let mut size = None;
+    let mut voxels = vec![];
+    // skip the MAIN header, its children follow its content
+    let mut at = offset(8 + 12, read_len(bytes, 12)?)?;
+    while at + 12 <= bytes.len() {
+        let id = &bytes[at..at + 4];
+        let content = read_len(bytes, at + 4)?;
+        let body = at + 12;
+        match id {
+            b"SIZE" if size.is_none() => {
+                let dims = [
+                    read_len(bytes, body)?,
+                    read_len(bytes, body + 4)?,
+                    read_len(bytes, body + 8)?,
+                ];
+                size = Some(dims.into_iter().max().unwrap());
+            }
+            b"XYZI" if voxels.is_empty() => {
+                let count = read_len(bytes, body)?;
+                let end = count
+                    .checked_mul(4)
+                    .and_then(|n| n.checked_add(body + 4))
+                    .ok_or("truncated XYZI chunk")?;
+                let data = bytes.get(body + 4..end).ok_or("truncated XYZI chunk")?;
+                voxels = data
+                    .chunks(4)
+                    .map(|v| (V3(v[0] as usize, v[1] as usize, v[2] as usize), v[3]))
+                    .collect();
+            }
+            _ => {}
+        }
+        at = offset(offset(body, content)?, read_len(bytes, at + 8)?)?;
+    }
+    let size = size.ok_or("missing SIZE chunk")?;
+    if size > MAX_SIZE {
+        return Err(format!("size {} above {}", size, MAX_SIZE));
+    }
+    let mut colors = voxels.iter().map(|v| v.1).collect::<Vec<_>>();
+    colors.sort();
+    colors.dedup();
+    let mut cells = vec![0; size * size * size];
+    for (v, c) in voxels {
+        if v.0 >= size || v.1 >= size || v.2 >= size {
+            return Err(format!("voxel {:?} outside of the size {}", v, size));
+        }
+        let label = colors.iter().position(|&x| x == c).unwrap() + 1;
+        cells[Cells::to_indexv(size, v)] = label;
+    }
+    Ok(PuzzleNumFormat::new(V3::cube(size), colors.len(), cells))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::sample_puzzle;
+    #[test]
+    fn test_vox_roundtrip() {
+        let puzzle = sample_puzzle();
+        let bytes = to_vox(&puzzle).unwrap();
+        let format = from_vox(&bytes).unwrap();
+        assert_eq!(format, PuzzleNumFormat::from_puzzle(&puzzle));
+
+        // one cell per piece, beyond the palette
+        let cells = (0..343).map(|i| if i < 256 { i + 1 } else { 0 }).collect();
+        let many = PuzzleNumFormat::new(V3::cube(7), 256, cells).to_puzzle();
+        assert!(to_vox(&many).is_err());
+    }
+    #[test]
+    fn test_vox_palette_gap() {
+        let puzzle = PuzzleNumFormat::from_block_code("222:2:01000200").to_puzzle();
+        let mut bytes = to_vox(&puzzle).unwrap();
+        let xyzi = bytes.windows(4).position(|w| w == b"XYZI").unwrap();
+        // pieces painted with palette 7 and 3 are numbered by palette order
+        bytes[xyzi + 16 + 3] = 7;
+        bytes[xyzi + 20 + 3] = 3;
+        let format = from_vox(&bytes).unwrap();
+        assert_eq!(format.to_block_code(), "222:2:02000100");
+        assert!(from_vox(b"nope").is_err());
+    }
+    #[test]
+    fn test_vox_malformed() {
+        let puzzle = PuzzleNumFormat::from_block_code("222:2:01000200").to_puzzle();
+        let bytes = to_vox(&puzzle).unwrap();
+        for len in 0..bytes.len() {
+            let _ = from_vox(&bytes[..len]);
+        }
+        let xyzi = bytes.windows(4).position(|w| w == b"XYZI").unwrap();
+        assert!(from_vox(&bytes[..xyzi + 20]).is_err());
+        // voxel beyond the SIZE chunk
+        let mut outside = bytes.clone();
+        outside[xyzi + 16] = 2;
+        assert!(from_vox(&outside).is_err());
+        // negative voxel count
+        let mut negative = bytes.clone();
+        negative[xyzi + 12..xyzi + 16].copy_from_slice(&(-1i32).to_le_bytes());
+        assert!(from_vox(&negative).is_err());
+        let mut huge = bytes.clone();
+        huge[xyzi + 4..xyzi + 8].copy_from_slice(&i32::MAX.to_le_bytes());
+        huge[xyzi + 8..xyzi + 12].copy_from_slice(&i32::MAX.to_le_bytes());
+        let _ = from_vox(&huge);
+        let size = bytes.windows(4).position(|w| w == b"SIZE").unwrap();
+        let mut large = bytes.clone();
+        large[size + 12..size + 16].copy_from_slice(&i32::MAX.to_le_bytes());
+        assert!(from_vox(&large).is_err());
+    }
+}
@@ -0,0 +1,71 @@
+use crate::cells::Cells;
+use crate::iters::V3Iter;
+use crate::mesh::Mesh;
+use crate::v3::V3F;
+
+/// Solid angle of the mesh seen from `p`, in full turns; about 1 inside, 0 outside
+pub fn winding_number(mesh: &Mesh, p: V3F) -> f64 {
+    let mut total = 0.0;
+    for [a, b, c] in mesh.triangles.iter() {
+        let (a, b, c) = (*a - p, *b - p, *c - p);
+        let (la, lb, lc) = (a.norm(), b.norm(), c.norm());
+        let numerator = a.dot(&b.cross(&c));
+        let denominator = la * lb * lc + a.dot(&b) * lc + b.dot(&c) * la + c.dot(&a) * lb;
+        total += 2.0 * numerator.atan2(denominator);
+    }
+    total / (4.0 * std::f64::consts::PI)
+}
+
+/// Target shape of `size`^3 voxels: the mesh is scaled to fit the cube and centered,
+/// a voxel is filled when its center is inside. Tolerates holes and flipped faces.
+pub fn voxelize(mesh: &Mesh, size: usize) -> Cells {
+    let (min, max) = mesh.bounding_box();
+    let extent = max - min;
+    let scale = extent.0.max(extent.1).max(extent.2) / size as f64;
+    let center = (min + max) * 0.5;
+    let half = size as f64 / 2.0;
+    let mut cells = Cells::empty(size);
+    for v in V3Iter::cube(size) {
+        let local = V3F::from(v) + V3F(0.5 - half, 0.5 - half, 0.5 - half);
+        let p = center + local * scale;
+        if winding_number(mesh, p).abs() > 0.5 {
+            cells.setv(v, true);
+        }
+    }
+    cells
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh::MeshOptions;
+    use crate::puzzle::Piece;
+    #[test]
+    fn test_voxelize_piece() {
+        let piece = Piece::from_str(
+            3,
+            "
+            xxx|...|...
+            x..|...|...
+            x..|x..|xxx",
+        );
+        let options = MeshOptions {
+            inset: 0.0,
+            bevel: 0.0,
+            ..MeshOptions::default()
+        };
+        let mesh = Mesh::from_piece(&piece, &options);
+        let cells = voxelize(&mesh, 3);
+        assert_eq!(cells.to_str(), piece.block.to_str());
+    }
+    #[test]
+    fn test_voxelize_flipped() {
+        let piece = Piece::from_str(2, "xx|xx\nxx|xx");
+        let mut mesh = Mesh::from_piece(&piece, &MeshOptions::default());
+        for t in mesh.triangles.iter_mut() {
+            t.swap(1, 2);
+        }
+        // a cube fills the whole grid whatever the resolution
+        assert_eq!(voxelize(&mesh, 4).count(), 64);
+    }
+}
@@ -0,0 +1,229 @@
+use serde::{Deserialize, Serialize};
+
+use crate::archive::SolveStats;
+use crate::features::Features;
+use crate::puzzle::{Puzzle, SolveResult};
+use crate::searcher::{EvalValue, Evaluator};
+
+/// Number of `Feature` variants
+const FEATURES: usize = 6;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Feature {
+    FirstRemove,
+    DropCount,
+    /// Moves once consecutive shifts of the same pieces are merged
+    Shrink,
+    /// Unit moves
+    All,
+    DeadEnds,
+    /// Variance of the cell counts of the pieces
+    SizeVariance,
+}
+impl Feature {
+    const ALL: [Feature; FEATURES] = [
+        Feature::FirstRemove,
+        Feature::DropCount,
+        Feature::Shrink,
+        Feature::All,
+        Feature::DeadEnds,
+        Feature::SizeVariance,
+    ];
+    fn name(&self) -> &'static str {
+        match self {
+            Feature::FirstRemove => "first_remove",
+            Feature::DropCount => "drop_count",
+            Feature::Shrink => "shrink",
+            Feature::All => "all",
+            Feature::DeadEnds => "dead_ends",
+            Feature::SizeVariance => "size_variance",
+        }
+    }
+    /// Letter in `to_path`
+    fn letter(&self) -> char {
+        match self {
+            Feature::FirstRemove => 'F',
+            Feature::DropCount => 'D',
+            Feature::Shrink => 'S',
+            Feature::All => 'A',
+            Feature::DeadEnds => 'E',
+            Feature::SizeVariance => 'V',
+        }
+    }
+    fn extract(&self, features: &Features) -> f64 {
+        match self {
+            Feature::FirstRemove => features.first_remove as f64,
+            Feature::DropCount => features.drop_count as f64,
+            Feature::Shrink => features.shrink as f64,
+            Feature::All => features.all as f64,
+            Feature::DeadEnds => features.dead_ends as f64,
+            Feature::SizeVariance => features.size_variance,
+        }
+    }
+    fn of_stats(&self, stats: &SolveStats) -> Option<f64> {
+        match self {
+            Feature::FirstRemove => Some(stats.first as f64),
+            Feature::Shrink => Some(stats.shrink as f64),
+            Feature::All => Some(stats.all as f64),
+            _ => None,
+        }
+    }
+}
+
+/// One component of the weighted sum
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Term {
+    pub feature: Feature,
+    pub weight: f64,
+    /// Puzzles with the feature outside of `min..=max` get the lowest value
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+}
+
+/// Weighted sum of features, from the run config instead of a new `Evaluator` per idea
+#[derive(Debug, Clone)]
+pub struct WeightedEvaluator {
+    pub terms: Vec<Term>,
+}
+impl WeightedEvaluator {
+    fn value(&self, mut extract: impl FnMut(Feature) -> Option<f64>) -> Option<WeightedValue> {
+        let mut value = WeightedValue::default();
+        let mut score = 0.0;
+        let mut rejected = false;
+        for term in self.terms.iter() {
+            let x = extract(term.feature)?;
+            let i = term.feature as usize;
+            value.features[i] = milli(x);
+            value.used |= 1 << i;
+            score += term.weight * x;
+            rejected |= term.min.is_some_and(|min| x < min) || term.max.is_some_and(|max| x > max);
+        }
+        value.score = if rejected { i64::MIN } else { milli(score) };
+        Some(value)
+    }
+}
+impl Evaluator for WeightedEvaluator {
+    type Value = WeightedValue;
+    fn evaluate(&self, puzzle: &Puzzle, result: &SolveResult) -> Self::Value {
+        let features = Features::of_moves(puzzle, result);
+        self.value(|feature| Some(feature.extract(&features)))
+            .unwrap()
+    }
+    fn stats_value(&self, stats: &SolveStats) -> Option<Self::Value> {
+        self.value(|feature| feature.of_stats(stats))
+    }
+}
+
+/// Thousandths, exact and totally ordered
+fn milli(x: f64) -> i64 {
+    (x * 1000.0).round() as i64
+}
+fn unmilli(x: i64) -> String {
+    if x % 1000 == 0 {
+        (x / 1000).to_string()
+    } else {
+        format!("{:.3}", x as f64 / 1000.0)
+    }
+}
+
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Serialize, Deserialize)]
+pub struct WeightedValue {
+    /// Weighted sum in thousandths, `i64::MIN` when out of the bounds of a term
+    pub score: i64,
+    /// In thousandths, by `Feature as usize`
+    pub features: [i64; FEATURES],
+    /// Bit `Feature as usize` set for the features in the sum
+    pub used: u8,
+}
+impl WeightedValue {
+    fn used(&self) -> impl Iterator<Item = (Feature, i64)> + '_ {
+        Feature::ALL
+            .into_iter()
+            .filter(|&f| self.used & (1 << f as usize) != 0)
+            .map(|f| (f, self.features[f as usize]))
+    }
+    fn score(&self) -> String {
+        match self.score {
+            i64::MIN => "out".to_owned(),
+            score => unmilli(score),
+        }
+    }
+}
+impl EvalValue for WeightedValue {
+    fn to_str(&self) -> String {
+        let mut s = format!("score={}", self.score());
+        for (feature, x) in self.used() {
+            s.push_str(&format!(" {}={}", feature.name(), unmilli(x)));
+        }
+        s
+    }
+    fn to_path(&self) -> String {
+        let mut s = format!("W{}_", self.score());
+        for (feature, x) in self.used() {
+            s.push_str(&format!("{}{}", feature.letter(), unmilli(x)));
+        }
+        s
+    }
+    fn to_scalar(&self) -> f64 {
+        self.score as f64 / 1000.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::searcher::{ShrinkStepEvaluator, ShrinkStepValue};
+    use crate::server::sample_puzzle;
+    #[test]
+    fn test_weighted() {
+        let terms: Vec<Term> = serde_json::from_str(
+            r#"[
+                {"feature": "first_remove", "weight": 10, "min": 1},
+                {"feature": "all", "weight": 0.5},
+                {"feature": "size_variance", "weight": -1}
+            ]"#,
+        )
+        .unwrap();
+        let evaluator = WeightedEvaluator { terms };
+        let puzzle = sample_puzzle();
+        let result = puzzle.solve();
+        let value = evaluator.evaluate(&puzzle, &result);
+        let ShrinkStepValue(first, _, all) = ShrinkStepEvaluator {}.evaluate(&puzzle, &result);
+        let sizes = puzzle
+            .pieces
+            .iter()
+            .map(|p| p.block.count() as f64)
+            .collect::<Vec<_>>();
+        let mean = sizes.iter().sum::<f64>() / sizes.len() as f64;
+        let variance = sizes.iter().map(|s| (s - mean).powi(2)).sum::<f64>() / sizes.len() as f64;
+        let score = 10.0 * first as f64 + 0.5 * all as f64 - variance;
+        assert_eq!(value.score, milli(score));
+        assert_eq!(
+            value.to_path(),
+            format!(
+                "W{}_F{}A{}V{}",
+                unmilli(milli(score)),
+                first,
+                all,
+                unmilli(milli(variance))
+            )
+        );
+        assert!(value.to_str().starts_with("score="));
+        // thresholds
+        let mut strict = evaluator.clone();
+        strict.terms[0].min = Some(first as f64 + 1.0);
+        let out = strict.evaluate(&puzzle, &result);
+        assert!(out < value);
+        assert!(out.to_path().starts_with("Wout_"));
+        // the variance needs the pieces
+        let stats = SolveStats::from_result(&puzzle, &result).unwrap();
+        assert!(evaluator.stats_value(&stats).is_none());
+        strict.terms.pop();
+        assert_eq!(
+            strict.stats_value(&stats),
+            Some(strict.evaluate(&puzzle, &result))
+        );
+    }
+}
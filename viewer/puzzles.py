@@ -1,6 +1,9 @@
+from json import load
 from pathlib import Path
 from re import findall
 
+SIDECAR_VERSION = 1
+
 source_path = Path(__file__).resolve()
 root_dir = source_path.parent.parent
 puzzles_dir = root_dir / "puzzles"
@@ -24,12 +27,46 @@ def load_puzzle(puzzle):
 
 
 def read_moves(puzzle):
+    sidecar = puzzle.with_suffix(".json")
+    if sidecar.exists():
+        return read_sidecar_moves(sidecar)
     with open(puzzle, "r") as f:
         for line in f:
             if line.startswith("//["):
                 return parse_moves(line)
 
 
+def read_sidecar_moves(sidecar):
+    with open(sidecar, "r") as f:
+        data = load(f)
+    if data["version"] > SIDECAR_VERSION:
+        raise ValueError(f"{sidecar}: unsupported version {data['version']}")
+    return sidecar_moves_to_s(data["solution"]["moves"])
+
+
+def sidecar_moves_to_s(moves):
+    # consecutive shifts of the same pieces in one group, as in the .pcad moves
+    groups = []
+    for move in moves:
+        translate = move["translate"]
+        if translate is None:
+            groups.append((None, move["pieces"][0]))
+            continue
+        shift = f"({translate['x']}, {translate['y']}, {translate['z']})"
+        last = groups[-1] if groups else None
+        if last is not None and last[0] == move["pieces"]:
+            last[1].append(shift)
+        else:
+            groups.append((move["pieces"], [shift]))
+    return "".join(group_to_s(pieces, rest) for pieces, rest in groups)
+
+
+def group_to_s(pieces, rest):
+    if pieces is None:
+        return f"!{rest}"
+    return f">{'_'.join(str(p) for p in pieces)} {', '.join(rest)}"
+
+
 def parse_moves(moves):
     return "".join(move_to_s(move) for move in findall(r"([A-Za-z]+)\((?:\[((?:\d+,? ?)+)\], \[((?:\(-?\d+, -?\d+, -?\d+\)(?:, )?)+)\]|(?:(\d+), \(((?:\d+,? ?)+)\)))\)", moves))
 